@@ -0,0 +1,369 @@
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::pixels::{ImageHeader, Pixels};
+use crate::png::Png;
+
+// Number of prefixes of the samples the chi-square attack is run on, to locate sequential embedding
+const CHI_SQUARE_STEPS: usize = 100;
+// Pairs of values with fewer samples than this are left out of the chi-square statistic
+const CHI_SQUARE_MIN_COUNT: u64 = 10;
+
+/// Estimates for one color channel of the image. Rates are fractions of the channel samples
+/// carrying hidden bits, from 0 to 1.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelAnalysis {
+    pub channel: String,
+    // Probability that the pairs of values 2k and 2k+1 have been equalized by LSB embedding
+    pub chi_square_p_value: f64,
+    // Fraction of the samples, from the start, over which the chi-square attack detects embedding
+    pub chi_square_rate: f64,
+    pub rs_rate: f64,
+    pub spa_rate: f64,
+}
+impl ChannelAnalysis {
+    // Estimated embedding rate, averaging RS analysis and sample pair analysis which are the
+    // most accurate for random embedding
+    pub fn estimated_rate(&self) -> f64 {
+        (self.rs_rate + self.spa_rate) / 2.0
+    }
+}
+
+// Runs the chi-square attack, RS analysis and sample pair analysis on each color channel of
+// a PNG. Only 8 and 16-bit grayscale or truecolor images are supported, 16-bit samples
+// being analyzed through their low byte.
+pub fn analyze(png: &Png) -> Result<Vec<ChannelAnalysis>, Box<dyn Error>> {
+    let pixels: Pixels = Pixels::from_png(png)?;
+    let header: ImageHeader = *pixels.header();
+    let names: &[&str] = if header.channels() >= 3 { &["red", "green", "blue"] } else { &["gray"] };
+    let offsets: Vec<usize> = pixels.color_sample_offsets()?;
+
+    let mut analyses: Vec<ChannelAnalysis> = vec![];
+    for (channel, name) in names.iter().enumerate() {
+        let samples: Vec<u8> = offsets
+            .iter()
+            .skip(channel)
+            .step_by(names.len())
+            .map(|offset: &usize| pixels.data()[*offset])
+            .collect();
+        let rows: Vec<&[u8]> = samples.chunks(header.width as usize).collect();
+        let (chi_square_p_value, chi_square_rate) = chi_square(&samples);
+        analyses.push(ChannelAnalysis{
+            channel: name.to_string(),
+            chi_square_p_value,
+            chi_square_rate,
+            rs_rate: rs_analysis(&rows),
+            spa_rate: sample_pair_analysis(&rows),
+        });
+    }
+    Ok(analyses)
+}
+
+// Chi-square attack (Westfeld & Pfitzmann): LSB embedding equalizes the frequencies of the
+// values 2k and 2k+1. Returns the p-value over all the samples, and the largest fraction of
+// the samples, from the start, whose p-value stays above 0.5.
+pub fn chi_square(samples: &[u8]) -> (f64, f64) {
+    let mut histogram: [u64; 256] = [0; 256];
+    let mut rate: f64 = 0.0;
+    let mut p_value: f64 = 0.0;
+    let mut counted: usize = 0;
+    for step in 1..=CHI_SQUARE_STEPS {
+        let end: usize = samples.len() * step / CHI_SQUARE_STEPS;
+        for sample in &samples[counted..end] {
+            histogram[*sample as usize] += 1;
+        }
+        counted = end;
+        p_value = chi_square_p_value(&histogram);
+        if p_value > 0.5 {
+            rate = step as f64 / CHI_SQUARE_STEPS as f64;
+        }
+    }
+    (p_value, rate)
+}
+
+fn chi_square_p_value(histogram: &[u64; 256]) -> f64 {
+    let mut statistic: f64 = 0.0;
+    let mut categories: usize = 0;
+    for pair in histogram.chunks(2) {
+        let total: u64 = pair[0] + pair[1];
+        if total < CHI_SQUARE_MIN_COUNT {
+            continue;
+        }
+        let expected: f64 = total as f64 / 2.0;
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return 0.0;
+    }
+    upper_regularized_gamma((categories - 1) as f64 / 2.0, statistic / 2.0)
+}
+
+// RS analysis (Fridrich, Goljan & Du): flipping LSBs changes the proportions of regular and
+// singular pixel groups in a way that depends on the embedding rate, which is recovered by
+// solving a quadratic equation.
+pub fn rs_analysis(rows: &[&[u8]]) -> f64 {
+    const MASK: [i8; 4] = [0, 1, 1, 0];
+    let negative_mask: [i8; 4] = MASK.map(|m: i8| -m);
+    let flipped: Vec<Vec<u8>> = rows.iter().map(|row: &&[u8]| row.iter().map(|x: &u8| x ^ 1).collect()).collect();
+    let flipped: Vec<&[u8]> = flipped.iter().map(|row: &Vec<u8>| row.as_slice()).collect();
+
+    let difference = |rows: &[&[u8]], mask: &[i8; 4]| -> f64 {
+        let (regular, singular, groups) = rs_counts(rows, mask);
+        if groups == 0 { 0.0 } else { (regular as f64 - singular as f64) / groups as f64 }
+    };
+    let d0: f64 = difference(rows, &MASK);
+    let d1: f64 = difference(&flipped, &MASK);
+    let n0: f64 = difference(rows, &negative_mask);
+    let n1: f64 = difference(&flipped, &negative_mask);
+
+    let a: f64 = 2.0 * (d1 + d0);
+    let b: f64 = n0 - n1 - d1 - 3.0 * d0;
+    let c: f64 = d0 - n0;
+    let x: f64 = match smallest_root(a, b, c) {
+        Some(x) => x,
+        None => return 0.0,
+    };
+    clamp_rate(x / (x - 0.5))
+}
+
+// Counts the regular and singular groups of 4 horizontally adjacent samples once the mask
+// is applied, along with the number of groups
+fn rs_counts(rows: &[&[u8]], mask: &[i8; 4]) -> (usize, usize, usize) {
+    let smoothness = |group: &[i16]| -> i16 {
+        group.windows(2).map(|pair: &[i16]| (pair[1] - pair[0]).abs()).sum()
+    };
+    let (mut regular, mut singular, mut groups) = (0, 0, 0);
+    for row in rows {
+        for group in row.chunks_exact(4) {
+            let original: Vec<i16> = group.iter().map(|x: &u8| *x as i16).collect();
+            let flipped: Vec<i16> = original
+                .iter()
+                .zip(mask.iter())
+                .map(|(x, m)| match m {
+                    1 => x ^ 1,
+                    -1 => ((x + 1) ^ 1) - 1,
+                    _ => *x,
+                })
+                .collect();
+            let (before, after) = (smoothness(&original), smoothness(&flipped));
+            if after > before {
+                regular += 1;
+            } else if after < before {
+                singular += 1;
+            }
+            groups += 1;
+        }
+    }
+    (regular, singular, groups)
+}
+
+// Sample pair analysis (Dumitrescu, Wu & Wang): the populations of horizontally adjacent
+// sample pairs are related by the embedding rate through a quadratic equation.
+pub fn sample_pair_analysis(rows: &[&[u8]]) -> f64 {
+    let (mut x, mut y, mut k, mut pairs) = (0.0, 0.0, 0.0, 0.0);
+    for row in rows {
+        for pair in row.windows(2) {
+            let (u, v) = (pair[0], pair[1]);
+            if (v % 2 == 0 && u < v) || (v % 2 == 1 && u > v) {
+                x += 1.0;
+            }
+            if (v % 2 == 0 && u > v) || (v % 2 == 1 && u < v) {
+                y += 1.0;
+            }
+            if u / 2 == v / 2 {
+                k += 1.0;
+            }
+            pairs += 1.0;
+        }
+    }
+    if k == 0.0 {
+        return 0.0;
+    }
+    match smallest_root(2.0 * k, 2.0 * (2.0 * x - pairs), y - x) {
+        Some(beta) => clamp_rate(2.0 * beta),
+        None => 0.0,
+    }
+}
+
+// Returns the root of ax² + bx + c with the smallest absolute value. Estimation noise may
+// leave the discriminant slightly negative, the real part of the complex roots then being used.
+fn smallest_root(a: f64, b: f64, c: f64) -> Option<f64> {
+    if a.abs() < f64::EPSILON {
+        return if b.abs() < f64::EPSILON { None } else { Some(-c / b) };
+    }
+    let discriminant: f64 = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Some(-b / (2.0 * a));
+    }
+    let (r1, r2) = ((-b + discriminant.sqrt()) / (2.0 * a), (-b - discriminant.sqrt()) / (2.0 * a));
+    Some(if r1.abs() < r2.abs() { r1 } else { r2 })
+}
+
+fn clamp_rate(rate: f64) -> f64 {
+    if rate.is_nan() { 0.0 } else { rate.clamp(0.0, 1.0) }
+}
+
+// Upper regularized incomplete gamma function Q(s, x), i.e. the probability for a chi-square
+// distribution with 2s degrees of freedom to exceed 2x (Numerical Recipes, gammq)
+fn upper_regularized_gamma(s: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix: f64 = (-x + s * x.ln() - ln_gamma(s)).exp();
+    if x < s + 1.0 {
+        // Series expansion of P(s, x)
+        let (mut term, mut sum, mut n) = (1.0 / s, 1.0 / s, s);
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * prefix).clamp(0.0, 1.0)
+    } else {
+        // Continued fraction of Q(s, x), with the modified Lentz method
+        let tiny: f64 = 1e-300;
+        let mut b: f64 = x + 1.0 - s;
+        let mut c: f64 = 1.0 / tiny;
+        let mut d: f64 = 1.0 / b;
+        let mut h: f64 = d;
+        for i in 1..1000 {
+            let an: f64 = -(i as f64) * (i as f64 - s);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny { d = tiny; }
+            c = b + an / c;
+            if c.abs() < tiny { c = tiny; }
+            d = 1.0 / d;
+            let delta: f64 = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (prefix * h).clamp(0.0, 1.0)
+    }
+}
+
+// Natural logarithm of the gamma function, with the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146, -86.50532032941677, 24.01409824083091,
+        -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5,
+    ];
+    let tmp: f64 = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series: f64 = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |acc: f64, (i, coefficient)| acc + coefficient / (x + 1.0 + i as f64));
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use crate::analysis::{self, ChannelAnalysis};
+    use crate::chunk::Chunk;
+    use crate::png::Png;
+
+    // A smooth 128x128 grayscale image with some noise and a processed histogram, as natural
+    // images are
+    fn cover() -> Vec<Vec<u8>> {
+        let mut state: u32 = 12345;
+        let mut noise = move || -> i32 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            ((state >> 16) % 3) as i32 - 1
+        };
+        (0..128)
+            .map(|y: i32| (0..128).map(|x: i32| {
+                let smooth: f64 = 100.0 + 40.0 * (x as f64 / 9.0).sin() + 30.0 * (y as f64 / 7.0).cos();
+                let value: f64 = (smooth.round() + noise() as f64).clamp(0.0, 255.0);
+                // A tone curve leaves gaps in the histogram, as image editing usually does
+                (255.0 * (value / 255.0).powf(0.8)).round() as u8
+            }).collect())
+            .collect()
+    }
+    // Replaces the LSB of the first `rate` fraction of the samples with pseudo-random bits
+    fn embed(rows: &[Vec<u8>], rate: f64) -> Vec<Vec<u8>> {
+        let mut state: u32 = 987654321;
+        let total: usize = rows.len() * rows[0].len();
+        let limit: usize = (total as f64 * rate) as usize;
+        let mut index: usize = 0;
+        rows.iter()
+            .map(|row: &Vec<u8>| row.iter().map(|x: &u8| {
+                index += 1;
+                if index > limit {
+                    return *x;
+                }
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (x & !1) | ((state >> 16) & 1) as u8
+            }).collect())
+            .collect()
+    }
+    fn as_rows(rows: &[Vec<u8>]) -> Vec<&[u8]> {
+        rows.iter().map(|row: &Vec<u8>| row.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_clean_image() {
+        let rows: Vec<Vec<u8>> = cover();
+        let samples: Vec<u8> = rows.concat();
+        assert!(analysis::chi_square(&samples).0 < 0.5);
+        assert!(analysis::rs_analysis(&as_rows(&rows)) < 0.15);
+        assert!(analysis::sample_pair_analysis(&as_rows(&rows)) < 0.15);
+    }
+    #[test]
+    fn test_embedded_image() {
+        for rate in [0.5, 1.0] {
+            let rows: Vec<Vec<u8>> = embed(&cover(), rate);
+            let rs: f64 = analysis::rs_analysis(&as_rows(&rows));
+            let spa: f64 = analysis::sample_pair_analysis(&as_rows(&rows));
+            assert!((rs - rate).abs() < 0.15, "RS estimated {} for {}", rs, rate);
+            assert!((spa - rate).abs() < 0.15, "SPA estimated {} for {}", spa, rate);
+        }
+        let (p_value, rate) = analysis::chi_square(&embed(&cover(), 1.0).concat());
+        assert!(p_value > 0.5);
+        assert!(rate > 0.9);
+    }
+    #[test]
+    fn test_analyze() {
+        // The cover repeated in the three channels of an RGB image
+        let filtered: Vec<u8> = cover()
+            .iter()
+            .flat_map(|row: &Vec<u8>| [0].into_iter().chain(row.iter().flat_map(|x: &u8| [*x; 3])))
+            .collect();
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        let idat: Chunk = Chunk::new(*b"IDAT", encoder.finish().unwrap());
+        let png: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 128, 0, 0, 0, 128, 8, 2, 0, 0, 0]),
+            idat.clone(),
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+
+        let analyses: Vec<ChannelAnalysis> = analysis::analyze(&png).unwrap();
+        assert_eq!(analyses.iter().map(|channel: &ChannelAnalysis| channel.channel.as_str()).collect::<Vec<&str>>(), ["red", "green", "blue"]);
+        assert!(analyses.iter().all(|channel: &ChannelAnalysis| channel.estimated_rate() < 0.15));
+
+        let palette: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 128, 0, 0, 0, 128, 8, 3, 0, 0, 0]),
+            idat,
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+        assert!(analysis::analyze(&palette).is_err());
+    }
+    #[test]
+    fn test_upper_regularized_gamma() {
+        // Chi-square with 2 degrees of freedom: Q = exp(-x / 2)
+        assert!((analysis::upper_regularized_gamma(1.0, 1.5) - (-1.5f64).exp()).abs() < 1e-9);
+        assert!((analysis::upper_regularized_gamma(5.0, 0.5) - 0.99982).abs() < 1e-4);
+    }
+}
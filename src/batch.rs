@@ -0,0 +1,142 @@
+use std::any::Any;
+use std::error::Error;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+/// Outcome of a command run on one file of a batch
+pub struct FileResult {
+    pub path: PathBuf,
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+}
+
+// Whether an input names several files: a directory or a glob pattern
+pub fn is_batch(input: &Path) -> bool {
+    input.is_dir() || is_pattern(input)
+}
+
+// Whether an input is a glob pattern, e.g. `assets/**/*.png`
+fn is_pattern(input: &Path) -> bool {
+    input.to_string_lossy().contains(['*', '?', '['])
+}
+
+// Expands a directory or a glob pattern into the files it names, sorted. Directories yield
+// their PNG files, descending into subdirectories when `recursive` is set.
+pub fn expand(input: &Path, recursive: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = if is_pattern(input) {
+        let mut files: Vec<PathBuf> = vec![];
+        for entry in glob::glob(&input.to_string_lossy())? {
+            let path: PathBuf = entry?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        files
+    } else {
+        let mut files: Vec<PathBuf> = vec![];
+        collect_pngs(input, recursive, &mut files)?;
+        files
+    };
+    if files.is_empty() {
+        return Err(format!("No file found in {}", input.display()).into());
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Adds the PNG files of a directory, by extension, to the list
+fn collect_pngs(directory: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(directory)? {
+        let entry: fs::DirEntry = entry?;
+        let path: PathBuf = entry.path();
+        // Symbolic links aren't followed, a link to a parent directory would loop forever
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                collect_pngs(&path, recursive, files)?;
+            }
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Runs a command on every file in parallel, buffering what it prints. A failure, panics
+// included, only stops the file it happened on, every result being returned in the order of
+// the files.
+pub fn run<F>(files: &[PathBuf], command: F) -> Vec<FileResult>
+where
+    F: Fn(&Path, &mut Vec<u8>) -> Result<(), Box<dyn Error>> + Sync,
+{
+    files.par_iter()
+        .map(|path: &PathBuf| {
+            let mut output: Vec<u8> = vec![];
+            let outcome: Result<Result<(), Box<dyn Error>>, Box<dyn Any + Send>> = panic::catch_unwind(AssertUnwindSafe(|| command(path, &mut output)));
+            let error: Option<String> = match outcome {
+                Ok(result) => result.err().map(|err| err.to_string()),
+                Err(payload) => Some(panic_message(payload.as_ref())),
+            };
+            FileResult{path: path.to_path_buf(), output, error}
+        })
+        .collect()
+}
+
+// Describes a panic from its payload, which is the message unless a custom one was given
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message: &str = payload.downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("Unexpected failure: {}", message)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::batch;
+
+    #[test]
+    fn test_expand_and_run() {
+        let directory: PathBuf = std::env::temp_dir().join(format!("img_msg_encoder_batch_{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        for file in ["b.png", "a.PNG", "notes.txt", "nested/c.png"] {
+            fs::write(directory.join(file), file).unwrap();
+        }
+
+        assert!(batch::is_batch(&directory));
+        assert!(!batch::is_batch(&directory.join("a.PNG")));
+        assert_eq!(batch::expand(&directory, false).unwrap(), vec![directory.join("a.PNG"), directory.join("b.png")]);
+        assert_eq!(batch::expand(&directory, true).unwrap().len(), 3);
+        let pattern: PathBuf = directory.join("**").join("*.png");
+        assert_eq!(batch::expand(&pattern, false).unwrap(), vec![directory.join("b.png"), directory.join("nested/c.png")]);
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&directory, directory.join("nested/loop")).unwrap();
+            assert_eq!(batch::expand(&directory, true).unwrap().len(), 3);
+        }
+
+        let files: Vec<PathBuf> = batch::expand(&directory, true).unwrap();
+        let results: Vec<batch::FileResult> = batch::run(&files, |path: &Path, output: &mut Vec<u8>| {
+            if path.ends_with("b.png") {
+                return Err("failed".into());
+            }
+            if path.ends_with("c.png") {
+                panic!("broken");
+            }
+            output.extend_from_slice(&fs::read(path)?);
+            Ok(())
+        });
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].output, b"a.PNG");
+        assert_eq!(results[1].error.as_deref(), Some("failed"));
+        assert_eq!(results[2].error.as_deref(), Some("Unexpected failure: broken"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,915 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use rand::Rng;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::Envelope;
+use crate::pixels::{ImageHeader, Pixels};
+use crate::png::{Frame, FrameControl, Png};
+use crate::registry;
+
+// Chunk type used when none is given: ancillary, private, safe to copy
+pub const DEFAULT_CHUNK_TYPE: &str = "ruSt";
+// Keyword of the tEXt chunks written by the text carrier
+pub const TEXT_KEYWORD: &str = "Comment";
+// Most bits the delay carrier hides in a single frame delay
+pub const MAX_DELAY_BITS: usize = 4;
+
+/// Where a new chunk is inserted in the chunks list, always between IHDR and IEND
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Placement {
+    #[default]
+    Random,
+    AfterHeader,
+    BeforeEnd,
+}
+
+/// A technique hiding bytes in a PNG. Carriers only move raw bytes around, making sense of
+/// them (envelopes, encryption, ...) is left to `stego::Encoder` and `stego::Decoder`.
+pub trait Carrier: Send + Sync {
+    // Name used to select this carrier, e.g. with the `--method` flag
+    fn name(&self) -> &str;
+    // Number of bytes this carrier can hide in the PNG, `None` if it is unbounded
+    fn capacity(&self, png: &Png) -> Result<Option<usize>, Box<dyn Error>>;
+    // Hides bytes in the PNG, chunk-based carriers inserting their chunk at the given placement
+    fn embed(&self, png: &mut Png, bytes: &[u8], placement: Placement) -> Result<(), Box<dyn Error>>;
+    // Returns the bytes hidden in the PNG, one entry per hidden payload
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>>;
+    // Removes the payloads this carrier hid in the PNG, returning how many were removed
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>>;
+    // Whether extracted bytes which aren't envelopes are still plain messages, as written
+    // before envelopes existed
+    fn reads_bare_messages(&self) -> bool {
+        false
+    }
+    // Whether embedding and removing rewrite critical chunks such as IDAT, which drops the
+    // unregistered ancillary chunks that aren't safe to copy
+    fn rewrites_image_data(&self) -> bool {
+        false
+    }
+    // Whether extracting decodes image data, which is slow on large images
+    fn decodes_image_data(&self) -> bool {
+        false
+    }
+}
+
+/// Carriers available by name
+#[derive(Clone)]
+pub struct CarrierRegistry {
+    carriers: BTreeMap<String, Arc<dyn Carrier>>,
+}
+impl CarrierRegistry {
+    // Creates an empty registry
+    pub fn new() -> CarrierRegistry {
+        CarrierRegistry{carriers: BTreeMap::new()}
+    }
+
+    // Adds a carrier, replacing any carrier previously registered with the same name
+    pub fn register(&mut self, carrier: Arc<dyn Carrier>) {
+        self.carriers.insert(carrier.name().to_string(), carrier);
+    }
+    // Returns the carrier registered with this name
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Carrier>, Box<dyn Error>> {
+        self.carriers.get(name).cloned().ok_or_else(|| {
+            format!("Unknown method: {} (available: {})", name, self.names().join(", ")).into()
+        })
+    }
+    // Names of the registered carriers, in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        self.carriers.keys().map(|name: &String| name.as_str()).collect()
+    }
+    // The registered carriers, in alphabetical order of their names
+    pub fn carriers(&self) -> Vec<Arc<dyn Carrier>> {
+        self.carriers.values().cloned().collect()
+    }
+}
+impl Default for CarrierRegistry {
+    // Creates a registry holding the built-in carriers
+    fn default() -> Self {
+        let mut registry: CarrierRegistry = CarrierRegistry::new();
+        registry.register(Arc::new(ChunkCarrier::default()));
+        registry.register(Arc::new(TextCarrier));
+        registry.register(Arc::new(TrailerCarrier));
+        registry.register(Arc::new(LsbCarrier));
+        registry.register(Arc::new(PaletteCarrier));
+        registry.register(Arc::new(FrameCarrier));
+        registry.register(Arc::new(DelayCarrier));
+        registry
+    }
+}
+
+// Looks up a built-in carrier by name. The chunk carrier is only available when given the
+// chunk type it hides bytes in.
+pub fn select(method: &str, chunk_type: Option<ChunkType>) -> Result<Arc<dyn Carrier>, Box<dyn Error>> {
+    let mut registry: CarrierRegistry = CarrierRegistry::default();
+    if let Some(chunk_type) = chunk_type {
+        registry.register(Arc::new(ChunkCarrier::new(chunk_type)));
+    }
+    registry.get(method)
+}
+
+// Inserts a chunk at the given placement
+fn insert_chunk(png: &mut Png, chunk: Chunk, placement: Placement) {
+    match placement {
+        Placement::Random => png.append_chunk_somewhere(chunk),
+        Placement::AfterHeader => png.insert_chunk(*png.insertion_range().start(), chunk),
+        Placement::BeforeEnd => png.insert_chunk(*png.insertion_range().end(), chunk),
+    }
+}
+
+/// Hides bytes as the data of a dedicated chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkCarrier {
+    chunk_type: ChunkType,
+}
+impl ChunkCarrier {
+    // Init function
+    pub fn new(chunk_type: ChunkType) -> ChunkCarrier {
+        ChunkCarrier{chunk_type}
+    }
+}
+impl Default for ChunkCarrier {
+    fn default() -> Self {
+        ChunkCarrier::new(ChunkType::from_str(DEFAULT_CHUNK_TYPE).unwrap())
+    }
+}
+impl Carrier for ChunkCarrier {
+    fn name(&self) -> &str {
+        "chunk"
+    }
+    fn capacity(&self, _png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        // The spec limits chunk lengths to 2^31 - 1 bytes
+        Ok(Some(i32::MAX as usize))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], placement: Placement) -> Result<(), Box<dyn Error>> {
+        check_hiding_type(&self.chunk_type)?;
+        insert_chunk(png, Chunk::new(self.chunk_type.bytes(), bytes.to_vec()), placement);
+        Ok(())
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        Ok(png.chunks()
+            .iter()
+            .filter(|chunk: &&Chunk| chunk.chunk_type() == &self.chunk_type)
+            .map(|chunk: &Chunk| chunk.data().to_vec())
+            .collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        Ok(png.remove_all_chunks(&self.chunk_type.to_string()).len())
+    }
+    fn reads_bare_messages(&self) -> bool {
+        true
+    }
+}
+
+// Checks that a chunk type can hold hidden data without making decoders reject or misread
+// the image: it must be ancillary, have a valid reserved bit and not be registered
+pub fn check_hiding_type(chunk_type: &ChunkType) -> Result<(), Box<dyn Error>> {
+    if let Some(info) = registry::lookup(chunk_type) {
+        return Err(format!(
+            "{} is a registered chunk type ({}), pick a private one such as {} or use --auto-type",
+            info.name, info.description, DEFAULT_CHUNK_TYPE
+        ).into());
+    }
+    // The closest valid ancillary chunk type, falling back on the default one when registered
+    let mut bytes: [u8; 4] = chunk_type.bytes();
+    bytes[0] = bytes[0].to_ascii_lowercase();
+    bytes[2] = bytes[2].to_ascii_uppercase();
+    let suggestion: String = match ChunkType::try_from(bytes) {
+        Ok(fixed) if !registry::is_registered(&fixed) => fixed.to_string(),
+        _ => String::from(DEFAULT_CHUNK_TYPE),
+    };
+    if chunk_type.is_critical() {
+        return Err(format!(
+            "{} is a critical chunk type, making decoders which don't know it reject the image: use {} instead",
+            chunk_type, suggestion
+        ).into());
+    }
+    if !chunk_type.is_reserved_bit_valid() {
+        return Err(format!(
+            "{} has its reserved bit set (lowercase third letter), which is invalid: use {} instead",
+            chunk_type, suggestion
+        ).into());
+    }
+    Ok(())
+}
+
+/// Hides bytes, base64 encoded, in a standard tEXt chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextCarrier;
+impl TextCarrier {
+    // Returns the decoded bytes of a tEXt chunk written by this carrier
+    fn decode(chunk: &Chunk) -> Option<Vec<u8>> {
+        if chunk.chunk_type().bytes() != *b"tEXt" {
+            return None;
+        }
+        let text: &[u8] = chunk.data().strip_prefix(TEXT_KEYWORD.as_bytes())?.strip_prefix(&[0])?;
+        BASE64.decode(text).ok()
+    }
+}
+impl Carrier for TextCarrier {
+    fn name(&self) -> &str {
+        "text"
+    }
+    fn capacity(&self, _png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        // Every 3 bytes take 4 once encoded
+        Ok(Some((i32::MAX as usize - TEXT_KEYWORD.len() - 1) / 4 * 3))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], placement: Placement) -> Result<(), Box<dyn Error>> {
+        let data: Vec<u8> = TEXT_KEYWORD.as_bytes()
+            .iter()
+            .chain([0].iter())
+            .chain(BASE64.encode(bytes).as_bytes().iter())
+            .copied().collect();
+        insert_chunk(png, Chunk::new(*b"tEXt", data), placement);
+        Ok(())
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        Ok(png.chunks().iter().filter_map(TextCarrier::decode).collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        let removed: Vec<Chunk> = png.retain_chunks(|chunk: &Chunk| {
+            !TextCarrier::decode(chunk).is_some_and(|bytes: Vec<u8>| Envelope::is_envelope(&bytes))
+        });
+        Ok(removed.len())
+    }
+}
+
+/// Hides bytes after the IEND chunk, where decoders stop reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrailerCarrier;
+impl Carrier for TrailerCarrier {
+    fn name(&self) -> &str {
+        "trailer"
+    }
+    fn capacity(&self, _png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        Ok(None)
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], _placement: Placement) -> Result<(), Box<dyn Error>> {
+        if !png.trailing_data().is_empty() {
+            return Err(format!("The PNG already has {} bytes of trailing data", png.trailing_data().len()).into());
+        }
+        png.set_trailing_data(bytes.to_vec());
+        Ok(())
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        if png.trailing_data().is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![png.trailing_data().to_vec()])
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        if !Envelope::is_envelope(png.trailing_data()) {
+            return Ok(0);
+        }
+        png.set_trailing_data(vec![]);
+        Ok(1)
+    }
+}
+
+/// Hides bytes in the least significant bit of each color sample, prefixed by their length
+/// *(4 bytes, big-endian)*. The IDAT chunks are re-encoded into a single one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LsbCarrier;
+impl LsbCarrier {
+    // Writes the bits of the bytes, most significant first, in the sample bytes at the offsets
+    fn write_bits(data: &mut [u8], offsets: &[usize], bytes: &[u8]) {
+        for (index, offset) in offsets.iter().take(bytes.len() * 8).enumerate() {
+            let bit: u8 = (bytes[index / 8] >> (7 - index % 8)) & 1;
+            data[*offset] = (data[*offset] & !1) | bit;
+        }
+    }
+    // Reads bytes from the bits of the sample bytes at the offsets
+    fn read_bits(data: &[u8], offsets: &[usize], length: usize) -> Vec<u8> {
+        offsets.chunks(8)
+            .take(length)
+            .map(|byte: &[usize]| byte.iter().fold(0, |acc: u8, offset: &usize| (acc << 1) | (data[*offset] & 1)))
+            .collect()
+    }
+    // Reads the hidden bytes, if their length prefix is consistent with the capacity
+    fn read(data: &[u8], offsets: &[usize]) -> Option<Vec<u8>> {
+        if offsets.len() < 32 {
+            return None;
+        }
+        let length: usize = u32::from_be_bytes(Self::read_bits(data, offsets, 4).try_into().ok()?) as usize;
+        if length == 0 || length > offsets.len() / 8 - 4 {
+            return None;
+        }
+        Some(Self::read_bits(data, &offsets[32..], length))
+    }
+}
+impl Carrier for LsbCarrier {
+    fn name(&self) -> &str {
+        "lsb"
+    }
+    fn capacity(&self, png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        let samples: usize = Pixels::from_png(png)?.color_sample_offsets()?.len();
+        Ok(Some((samples / 8).saturating_sub(4)))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], _placement: Placement) -> Result<(), Box<dyn Error>> {
+        let mut pixels: Pixels = Pixels::from_png(png)?;
+        let offsets: Vec<usize> = pixels.color_sample_offsets()?;
+        let capacity: usize = (offsets.len() / 8).saturating_sub(4);
+        if bytes.len() > capacity {
+            return Err(format!(
+                "Payload too large for the lsb method ({} bytes, capacity of {} bytes)",
+                bytes.len(), capacity
+            ).into());
+        }
+
+        let framed: Vec<u8> = (bytes.len() as u32)
+            .to_be_bytes()
+            .iter()
+            .chain(bytes.iter())
+            .copied().collect();
+        Self::write_bits(pixels.data_mut(), &offsets, &framed);
+        pixels.write_to(png)
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let pixels: Pixels = Pixels::from_png(png)?;
+        let offsets: Vec<usize> = pixels.color_sample_offsets()?;
+        Ok(Self::read(pixels.data(), &offsets).into_iter().collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        let mut pixels: Pixels = Pixels::from_png(png)?;
+        let offsets: Vec<usize> = pixels.color_sample_offsets()?;
+        let hidden: Vec<u8> = match Self::read(pixels.data(), &offsets) {
+            Some(hidden) if Envelope::is_envelope(&hidden) => hidden,
+            _ => return Ok(0),
+        };
+
+        // Clear the length prefix, and scramble the bits which held the payload
+        let mut noise: Vec<u8> = vec![0; 4 + hidden.len()];
+        rand::thread_rng().fill(&mut noise[4..]);
+        Self::write_bits(pixels.data_mut(), &offsets, &noise);
+        pixels.write_to(png)?;
+        Ok(1)
+    }
+    fn rewrites_image_data(&self) -> bool {
+        true
+    }
+    fn decodes_image_data(&self) -> bool {
+        true
+    }
+}
+
+/// Hides bytes in the least significant bit of each sample of the palette entries, prefixed by
+/// their length *(4 bytes, big-endian)*. Only indexed-color images are supported: the PLTE
+/// chunk is rewritten, but the image data is left untouched. Low capacity, at most 3 bits per
+/// palette entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PaletteCarrier;
+impl PaletteCarrier {
+    // Index and content of the PLTE chunk of an indexed-color image, `None` for other images
+    fn palette(png: &Png) -> Result<Option<(usize, Chunk)>, Box<dyn Error>> {
+        if ImageHeader::from_png(png)?.color_type != 3 {
+            return Ok(None);
+        }
+        png.chunks()
+            .into_iter()
+            .enumerate()
+            .find(|(_, chunk): &(usize, Chunk)| chunk.chunk_type().bytes() == *b"PLTE")
+            .map(Some)
+            .ok_or_else(|| "No PLTE chunk found".into())
+    }
+    // Replaces the PLTE chunk, which is critical: the unregistered chunks that aren't safe to
+    // copy are dropped
+    fn write_palette(png: &mut Png, index: usize, data: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        png.remove_first_chunk("PLTE")?;
+        png.insert_chunk(index, Chunk::new(*b"PLTE", data));
+        png.strip_unsafe_to_copy();
+        Ok(())
+    }
+    // Offsets of the palette samples, all of them holding a bit
+    fn offsets(palette: &Chunk) -> Vec<usize> {
+        (0..palette.data().len()).collect()
+    }
+}
+impl Carrier for PaletteCarrier {
+    fn name(&self) -> &str {
+        "palette"
+    }
+    fn capacity(&self, png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        let (_, palette) = Self::palette(png)?.ok_or("Not an indexed-color image")?;
+        Ok(Some((Self::offsets(&palette).len() / 8).saturating_sub(4)))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], _placement: Placement) -> Result<(), Box<dyn Error>> {
+        let (index, palette) = Self::palette(png)?.ok_or("Not an indexed-color image")?;
+        let offsets: Vec<usize> = Self::offsets(&palette);
+        let capacity: usize = (offsets.len() / 8).saturating_sub(4);
+        if bytes.len() > capacity {
+            return Err(format!(
+                "Payload too large for the palette method ({} bytes, capacity of {} bytes)",
+                bytes.len(), capacity
+            ).into());
+        }
+
+        let framed: Vec<u8> = (bytes.len() as u32)
+            .to_be_bytes()
+            .iter()
+            .chain(bytes.iter())
+            .copied().collect();
+        let mut data: Vec<u8> = palette.data().to_vec();
+        LsbCarrier::write_bits(&mut data, &offsets, &framed);
+        Self::write_palette(png, index, data)
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let Some((_, palette)) = Self::palette(png)? else {
+            return Ok(vec![]);
+        };
+        Ok(LsbCarrier::read(palette.data(), &Self::offsets(&palette)).into_iter().collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        let Some((index, palette)) = Self::palette(png)? else {
+            return Ok(0);
+        };
+        let offsets: Vec<usize> = Self::offsets(&palette);
+        let hidden: Vec<u8> = match LsbCarrier::read(palette.data(), &offsets) {
+            Some(hidden) if Envelope::is_envelope(&hidden) => hidden,
+            _ => return Ok(0),
+        };
+
+        let mut noise: Vec<u8> = vec![0; 4 + hidden.len()];
+        rand::thread_rng().fill(&mut noise[4..]);
+        let mut data: Vec<u8> = palette.data().to_vec();
+        LsbCarrier::write_bits(&mut data, &offsets, &noise);
+        Self::write_palette(png, index, data)?;
+        Ok(1)
+    }
+    fn rewrites_image_data(&self) -> bool {
+        true
+    }
+}
+
+/// Hides bytes in the least significant bit of each color sample of the frames held by fdAT
+/// chunks, prefixed by their length *(4 bytes, big-endian)*. The default image, all that
+/// decoders without APNG support display, is left untouched along with the timing of every
+/// frame. Each frame is re-encoded into a single fdAT chunk, and the sequence renumbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameCarrier;
+// A frame along with its decoded pixels
+type DecodedFrame = (Frame, Pixels);
+impl FrameCarrier {
+    // Decodes the frames held by fdAT chunks, `None` for a still image
+    fn frames(png: &Png) -> Result<Option<Vec<DecodedFrame>>, Box<dyn Error>> {
+        let Some(animation) = png.animation()? else {
+            return Ok(None);
+        };
+        let header: ImageHeader = ImageHeader::from_png(png)?;
+        animation.frames
+            .into_iter()
+            .filter(|frame: &Frame| !frame.default_image && !frame.data_indexes.is_empty())
+            .map(|frame: Frame| {
+                let frame_header: ImageHeader = ImageHeader{width: frame.control.width, height: frame.control.height, ..header};
+                let pixels: Pixels = Pixels::decode(frame_header, &png.frame_data(&frame))?;
+                Ok((frame, pixels))
+            })
+            .collect::<Result<Vec<DecodedFrame>, Box<dyn Error>>>()
+            .map(Some)
+    }
+    // Concatenates the data of the frames, along with the offsets of their color sample bytes
+    fn samples(frames: &[DecodedFrame]) -> Result<(Vec<u8>, Vec<usize>), Box<dyn Error>> {
+        let mut data: Vec<u8> = vec![];
+        let mut offsets: Vec<usize> = vec![];
+        for (_, pixels) in frames {
+            let start: usize = data.len();
+            offsets.extend(pixels.color_sample_offsets()?.into_iter().map(|offset: usize| start + offset));
+            data.extend_from_slice(pixels.data());
+        }
+        Ok((data, offsets))
+    }
+    // Splits the concatenated data back into the frames, and writes them to the PNG
+    fn write_frames(png: &mut Png, mut frames: Vec<DecodedFrame>, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut start: usize = 0;
+        for (_, pixels) in frames.iter_mut() {
+            let length: usize = pixels.data().len();
+            pixels.data_mut().copy_from_slice(&data[start..(start + length)]);
+            start += length;
+        }
+        // Last frames first, so that the chunk indexes of the others stay valid
+        for (frame, pixels) in frames.iter().rev() {
+            png.set_frame_data(frame, &pixels.encode()?)?;
+        }
+        Ok(())
+    }
+}
+impl Carrier for FrameCarrier {
+    fn name(&self) -> &str {
+        "frames"
+    }
+    fn capacity(&self, png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        let frames: Vec<DecodedFrame> = Self::frames(png)?.ok_or("Not an animated PNG (no acTL chunk found)")?;
+        let (_, offsets) = Self::samples(&frames)?;
+        Ok(Some((offsets.len() / 8).saturating_sub(4)))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], _placement: Placement) -> Result<(), Box<dyn Error>> {
+        let frames: Vec<DecodedFrame> = Self::frames(png)?.ok_or("Not an animated PNG (no acTL chunk found)")?;
+        let (mut data, offsets) = Self::samples(&frames)?;
+        let capacity: usize = (offsets.len() / 8).saturating_sub(4);
+        if bytes.len() > capacity {
+            return Err(format!(
+                "Payload too large for the frames method ({} bytes, capacity of {} bytes)",
+                bytes.len(), capacity
+            ).into());
+        }
+
+        let framed: Vec<u8> = (bytes.len() as u32)
+            .to_be_bytes()
+            .iter()
+            .chain(bytes.iter())
+            .copied().collect();
+        LsbCarrier::write_bits(&mut data, &offsets, &framed);
+        Self::write_frames(png, frames, &data)
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let Some(frames) = Self::frames(png)? else {
+            return Ok(vec![]);
+        };
+        let (data, offsets) = Self::samples(&frames)?;
+        Ok(LsbCarrier::read(&data, &offsets).into_iter().collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        let Some(frames) = Self::frames(png)? else {
+            return Ok(0);
+        };
+        let (mut data, offsets) = Self::samples(&frames)?;
+        let hidden: Vec<u8> = match LsbCarrier::read(&data, &offsets) {
+            Some(hidden) if Envelope::is_envelope(&hidden) => hidden,
+            _ => return Ok(0),
+        };
+
+        let mut noise: Vec<u8> = vec![0; 4 + hidden.len()];
+        rand::thread_rng().fill(&mut noise[4..]);
+        LsbCarrier::write_bits(&mut data, &offsets, &noise);
+        Self::write_frames(png, frames, &data)?;
+        Ok(1)
+    }
+    fn decodes_image_data(&self) -> bool {
+        true
+    }
+}
+
+/// Hides bytes in the delays of the frames of an animated PNG, prefixed by their length *(2 bytes,
+/// big-endian)*. Each delay fraction is written as its lowest terms multiplied by a factor picked
+/// among equivalent fractions, encoding up to `MAX_DELAY_BITS` bits while leaving the duration,
+/// and so playback, unchanged. Low capacity, but no pixel is touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DelayCarrier;
+// A frame along with the number of bits its delay holds
+type DelayFrame = (Frame, usize);
+impl DelayCarrier {
+    // Number of bits a delay fraction in lowest terms can hold, as both its terms must still fit
+    // in 2 bytes once multiplied by up to 2^bits
+    fn delay_bits(num: u16, den: u16) -> usize {
+        let largest: usize = num.max(den) as usize;
+        (0..=MAX_DELAY_BITS).rev().find(|bits: &usize| largest << bits <= u16::MAX as usize).unwrap_or(0)
+    }
+    // The frames of the animation along with the number of bits each delay holds, `None` for a
+    // still image
+    fn frames(png: &Png) -> Result<Option<Vec<DelayFrame>>, Box<dyn Error>> {
+        let Some(animation) = png.animation()? else {
+            return Ok(None);
+        };
+        Ok(Some(animation.frames
+            .into_iter()
+            .map(|frame: Frame| {
+                let (num, den, _) = frame.control.reduced_delay();
+                (frame, Self::delay_bits(num, den))
+            })
+            .collect()))
+    }
+    // Reads the bits held by the delays, most significant first
+    fn read_bits(frames: &[DelayFrame]) -> Vec<u8> {
+        frames.iter()
+            .flat_map(|(frame, bits)| {
+                let (_, _, factor) = frame.control.reduced_delay();
+                let value: u16 = factor - 1;
+                (0..*bits).rev().map(move |bit: usize| ((value >> bit) & 1) as u8)
+            })
+            .collect()
+    }
+    // Writes bits in the delays, most significant first. Delays left without bits are written
+    // in lowest terms.
+    fn write_bits(png: &mut Png, frames: &[DelayFrame], bits: &[u8]) {
+        let mut bits = bits.iter();
+        for (frame, count) in frames {
+            let value: u16 = (0..*count).fold(0, |acc: u16, _| (acc << 1) | *bits.next().unwrap_or(&0) as u16);
+            let (num, den, _) = frame.control.reduced_delay();
+            let control: FrameControl = FrameControl{
+                delay_num: num * (value + 1),
+                delay_den: den * (value + 1),
+                ..frame.control
+            };
+            png.set_frame_control(frame, control);
+        }
+    }
+    // Reads the hidden bytes, if their length prefix is consistent with the capacity
+    fn read(frames: &[DelayFrame]) -> Option<Vec<u8>> {
+        let bits: Vec<u8> = Self::read_bits(frames);
+        let bytes: Vec<u8> = bits.chunks_exact(8)
+            .map(|byte: &[u8]| byte.iter().fold(0, |acc: u8, bit: &u8| (acc << 1) | bit))
+            .collect();
+        if bytes.len() < 2 {
+            return None;
+        }
+        let length: usize = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        if length == 0 || length > bytes.len() - 2 {
+            return None;
+        }
+        Some(bytes[2..(2 + length)].to_vec())
+    }
+    // Number of bytes the delays can hide, without the length prefix
+    fn capacity_of(frames: &[DelayFrame]) -> usize {
+        (frames.iter().map(|(_, bits)| bits).sum::<usize>() / 8).saturating_sub(2)
+    }
+}
+impl Carrier for DelayCarrier {
+    fn name(&self) -> &str {
+        "delay"
+    }
+    fn capacity(&self, png: &Png) -> Result<Option<usize>, Box<dyn Error>> {
+        let frames: Vec<DelayFrame> = Self::frames(png)?.ok_or("Not an animated PNG (no acTL chunk found)")?;
+        Ok(Some(Self::capacity_of(&frames)))
+    }
+    fn embed(&self, png: &mut Png, bytes: &[u8], _placement: Placement) -> Result<(), Box<dyn Error>> {
+        let frames: Vec<DelayFrame> = Self::frames(png)?.ok_or("Not an animated PNG (no acTL chunk found)")?;
+        let capacity: usize = Self::capacity_of(&frames);
+        if bytes.len() > capacity {
+            return Err(format!(
+                "Payload too large for the delay method ({} bytes, capacity of {} bytes)",
+                bytes.len(), capacity
+            ).into());
+        }
+
+        let bits: Vec<u8> = (bytes.len() as u16)
+            .to_be_bytes()
+            .iter()
+            .chain(bytes.iter())
+            .flat_map(|byte: &u8| (0..8).rev().map(move |bit: usize| (byte >> bit) & 1))
+            .collect();
+        Self::write_bits(png, &frames, &bits);
+        Ok(())
+    }
+    fn extract(&self, png: &Png) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let Some(frames) = Self::frames(png)? else {
+            return Ok(vec![]);
+        };
+        Ok(Self::read(&frames).into_iter().collect())
+    }
+    fn remove(&self, png: &mut Png) -> Result<usize, Box<dyn Error>> {
+        let Some(frames) = Self::frames(png)? else {
+            return Ok(0);
+        };
+        if !Self::read(&frames).is_some_and(|hidden: Vec<u8>| Envelope::is_envelope(&hidden)) {
+            return Ok(0);
+        }
+        // Delays in lowest terms hold no bits
+        Self::write_bits(png, &frames, &[]);
+        Ok(1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use flate2::write::ZlibEncoder;
+
+    use crate::carrier::{Carrier, CarrierRegistry, ChunkCarrier, DelayCarrier, FrameCarrier, LsbCarrier, PaletteCarrier, Placement, TextCarrier, TrailerCarrier};
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::envelope::Envelope;
+    use crate::pixels::Pixels;
+    use crate::png::{Frame, FrameControl, Png};
+
+    // A 16x16 RGBA image
+    fn testing_png() -> Png {
+        let filtered: Vec<u8> = (0..16)
+            .flat_map(|y: u8| [0].into_iter().chain((0..64).map(move |x: u8| x.wrapping_mul(3).wrapping_add(y))))
+            .collect();
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 16, 0, 0, 0, 16, 8, 6, 0, 0, 0]),
+            Chunk::new(*b"IDAT", encoder.finish().unwrap()),
+            Chunk::new(*b"IEND", vec![]),
+        ])
+    }
+    // A 16x16 RGBA APNG whose default image is followed by two frames held by fdAT chunks, the
+    // last one split in two
+    fn testing_apng() -> Png {
+        let png: Png = testing_png();
+        let data: Vec<u8> = png.chunk_by_type("IDAT").unwrap().data().to_vec();
+        let control = |sequence_number: u32| -> Chunk {
+            let control: FrameControl = FrameControl{
+                sequence_number, width: 16, height: 16, x_offset: 0, y_offset: 0,
+                delay_num: 1, delay_den: 10, dispose_op: 0, blend_op: 0,
+            };
+            Chunk::new(*b"fcTL", control.to_bytes())
+        };
+        let frame_data = |sequence_number: u32, bytes: &[u8]| -> Chunk {
+            Chunk::new(*b"fdAT", sequence_number.to_be_bytes().iter().chain(bytes.iter()).copied().collect())
+        };
+        Png::from_chunks(vec![
+            png.chunk_by_type("IHDR").unwrap().clone(),
+            Chunk::new(*b"acTL", vec![0, 0, 0, 3, 0, 0, 0, 0]),
+            control(0),
+            Chunk::new(*b"IDAT", data.clone()),
+            control(1),
+            frame_data(2, &data),
+            control(3),
+            frame_data(4, &data[..10]),
+            frame_data(5, &data[10..]),
+            Chunk::new(*b"IEND", vec![]),
+        ])
+    }
+    fn envelope_bytes() -> Vec<u8> {
+        Envelope::from_message("Hidden").to_bytes().unwrap()
+    }
+
+    fn check_round_trip(carrier: &dyn Carrier) {
+        let mut png: Png = testing_png();
+        carrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        let mut png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        assert_eq!(carrier.extract(&png).unwrap(), vec![envelope_bytes()]);
+
+        assert_eq!(carrier.remove(&mut png).unwrap(), 1);
+        assert!(carrier.extract(&png).unwrap().iter().all(|bytes: &Vec<u8>| !Envelope::is_envelope(bytes)));
+    }
+
+    #[test]
+    fn test_chunk_carrier() {
+        check_round_trip(&ChunkCarrier::new(ChunkType::from_str("abCd").unwrap()));
+        for chunk_type in ["tEXt", "RuSt", "rust"] {
+            let carrier: ChunkCarrier = ChunkCarrier::new(ChunkType::from_str(chunk_type).unwrap());
+            assert!(carrier.embed(&mut testing_png(), &envelope_bytes(), Placement::BeforeEnd).is_err());
+        }
+    }
+    #[test]
+    fn test_text_carrier() {
+        check_round_trip(&TextCarrier);
+
+        // Removal leaves the other chunks and the trailing data alone
+        let mut png: Png = testing_png();
+        png.set_trailing_data(vec![1, 2, 3]);
+        TextCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        assert_eq!(TextCarrier.remove(&mut png).unwrap(), 1);
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.trailing_data(), &[1, 2, 3]);
+    }
+    #[test]
+    fn test_trailer_carrier() {
+        check_round_trip(&TrailerCarrier);
+    }
+    #[test]
+    fn test_lsb_carrier() {
+        check_round_trip(&LsbCarrier);
+    }
+    #[test]
+    fn test_lsb_carrier_drops_unsafe_to_copy_chunks() {
+        let mut png: Png = testing_png();
+        png.insert_chunk(1, Chunk::new(*b"abCD", vec![1]));
+        png.insert_chunk(1, Chunk::new(*b"abCd", vec![2]));
+        png.insert_chunk(1, Chunk::new(*b"tRNS", vec![0, 1, 0, 2, 0, 3]));
+        png.insert_chunk(1, Chunk::new(*b"acTL", vec![0, 0, 0, 1, 0, 0, 0, 0]));
+        LsbCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        assert!(png.chunk_by_type("abCd").is_some());
+        assert!(png.chunk_by_type("abCD").is_none());
+        // Registered chunks are understood, unsafe to copy or not
+        assert!(png.chunk_by_type("tRNS").is_some());
+        assert!(png.chunk_by_type("acTL").is_some());
+        assert!(LsbCarrier.rewrites_image_data());
+    }
+    #[test]
+    fn test_lsb_carrier_only_alters_color_lsbs() {
+        let mut png: Png = testing_png();
+        LsbCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        let before: Pixels = Pixels::from_png(&testing_png()).unwrap();
+        let after: Pixels = Pixels::from_png(&png).unwrap();
+        for (index, (old, new)) in before.data().iter().zip(after.data().iter()).enumerate() {
+            assert_eq!(old >> 1, new >> 1);
+            if index % 4 == 3 {
+                assert_eq!(old, new);
+            }
+        }
+    }
+    #[test]
+    fn test_lsb_capacity() {
+        let png: Png = testing_png();
+        assert_eq!(LsbCarrier.capacity(&png).unwrap(), Some(16 * 16 * 3 / 8 - 4));
+        let mut png: Png = testing_png();
+        assert!(LsbCarrier.embed(&mut png, &[0; 93], Placement::Random).is_err());
+    }
+
+    #[test]
+    fn test_frame_carrier() {
+        let mut png: Png = testing_apng();
+        assert_eq!(FrameCarrier.capacity(&png).unwrap(), Some(2 * 16 * 16 * 3 / 8 - 4));
+        FrameCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        let mut png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        assert_eq!(FrameCarrier.extract(&png).unwrap(), vec![envelope_bytes()]);
+        assert!(png.check_sequence().is_empty());
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().as_bytes(), testing_apng().chunk_by_type("IDAT").unwrap().as_bytes());
+        assert_eq!(png.chunks().len(), 9);
+
+        assert_eq!(FrameCarrier.remove(&mut png).unwrap(), 1);
+        assert!(FrameCarrier.extract(&png).unwrap().iter().all(|bytes: &Vec<u8>| !Envelope::is_envelope(bytes)));
+        assert!(png.check_sequence().is_empty());
+    }
+    #[test]
+    fn test_delay_carrier() {
+        // 160 frames with assorted delays: 40000/1 holds no bits, the others 4
+        let delays: [(u16, u16); 4] = [(1, 10), (30, 0), (0, 0), (40000, 1)];
+        let mut chunks: Vec<Chunk> = vec![
+            testing_png().chunk_by_type("IHDR").unwrap().clone(),
+            Chunk::new(*b"acTL", [160u32, 0].iter().flat_map(|value: &u32| value.to_be_bytes()).collect()),
+        ];
+        for index in 0..160 {
+            let (delay_num, delay_den) = delays[index % 4];
+            let control: FrameControl = FrameControl{
+                sequence_number: 2 * index as u32, width: 16, height: 16, x_offset: 0, y_offset: 0,
+                delay_num, delay_den, dispose_op: 0, blend_op: 0,
+            };
+            chunks.push(Chunk::new(*b"fcTL", control.to_bytes()));
+            chunks.push(Chunk::new(*b"fdAT", (2 * index as u32 + 1).to_be_bytes().to_vec()));
+        }
+        chunks.push(Chunk::new(*b"IEND", vec![]));
+        let original: Png = Png::from_chunks(chunks);
+        let delays = |png: &Png| -> Vec<f64> {
+            png.animation().unwrap().unwrap().frames.iter().map(|frame: &Frame| frame.control.delay()).collect()
+        };
+
+        let mut png: Png = original.clone();
+        assert_eq!(DelayCarrier.capacity(&png).unwrap(), Some(120 * 4 / 8 - 2));
+        DelayCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        let mut png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        assert_eq!(DelayCarrier.extract(&png).unwrap(), vec![envelope_bytes()]);
+        assert_eq!(delays(&png), delays(&original));
+        assert!(png.check_sequence().is_empty());
+
+        assert_eq!(DelayCarrier.remove(&mut png).unwrap(), 1);
+        assert!(DelayCarrier.extract(&png).unwrap().is_empty());
+        assert_eq!(delays(&png), delays(&original));
+        assert!(DelayCarrier.embed(&mut png, &[0; 59], Placement::Random).is_err());
+        assert!(DelayCarrier.extract(&testing_png()).unwrap().is_empty());
+        assert!(DelayCarrier.capacity(&testing_png()).is_err());
+    }
+    #[test]
+    fn test_frame_carrier_on_still_image() {
+        let mut png: Png = testing_png();
+        assert!(FrameCarrier.capacity(&png).is_err());
+        assert!(FrameCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).is_err());
+        assert!(FrameCarrier.extract(&png).unwrap().is_empty());
+        assert_eq!(FrameCarrier.remove(&mut png).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_palette_carrier() {
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&(0..16).flat_map(|y: u8| [0].into_iter().chain((0..16).map(move |x: u8| y * 16 + x))).collect::<Vec<u8>>()).unwrap();
+        let original: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 16, 0, 0, 0, 16, 8, 3, 0, 0, 0]),
+            Chunk::new(*b"PLTE", (0..=255).flat_map(|entry: u8| [entry, 255 - entry, entry / 2]).collect()),
+            Chunk::new(*b"IDAT", encoder.finish().unwrap()),
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+        let mut png: Png = original.clone();
+        assert_eq!(PaletteCarrier.capacity(&png).unwrap(), Some(256 * 3 / 8 - 4));
+        PaletteCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).unwrap();
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().as_bytes(), original.chunk_by_type("IDAT").unwrap().as_bytes());
+        assert_eq!(PaletteCarrier.extract(&png).unwrap(), vec![envelope_bytes()]);
+
+        assert_eq!(PaletteCarrier.remove(&mut png).unwrap(), 1);
+        assert!(PaletteCarrier.extract(&png).unwrap().is_empty());
+        assert!(PaletteCarrier.embed(&mut png, &[0; 93], Placement::Random).is_err());
+    }
+    #[test]
+    fn test_palette_carrier_on_truecolor_image() {
+        let mut png: Png = testing_png();
+        assert!(PaletteCarrier.capacity(&png).is_err());
+        assert!(PaletteCarrier.embed(&mut png, &envelope_bytes(), Placement::Random).is_err());
+        assert!(PaletteCarrier.extract(&png).unwrap().is_empty());
+        assert_eq!(PaletteCarrier.remove(&mut png).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry: CarrierRegistry = CarrierRegistry::default();
+        assert_eq!(registry.names(), vec!["chunk", "delay", "frames", "lsb", "palette", "text", "trailer"]);
+        assert!(registry.get("dct").is_err());
+
+        registry.register(Arc::new(ChunkCarrier::new(ChunkType::from_str("abCd").unwrap())));
+        let mut png: Png = testing_png();
+        registry.get("chunk").unwrap().embed(&mut png, &envelope_bytes(), Placement::BeforeEnd).unwrap();
+        assert!(png.chunk_by_type("abCd").is_some());
+    }
+}
@@ -1,7 +1,15 @@
 use crc::{Crc, CRC_32_ISO_HDLC};
 use std::error::Error;
 use std::fmt;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use crate::chunk_type::ChunkType;
+use crate::envelope::Envelope;
+use crate::known_chunk::KnownChunk;
+use crate::pixels::ImageHeader;
+use crate::registry::{self, ChunkInfo};
+
+// Longest text kept in a chunk summary
+const SUMMARY_TEXT_LENGTH: usize = 40;
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -13,12 +21,7 @@ pub struct Chunk {
 impl Chunk {
     // Init function
     pub fn new(chunk_type: [u8; 4], data: Vec<u8>) -> Chunk {
-        let bytes: Vec<u8> = chunk_type
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect();
-        let crc: u32 = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&bytes);
+        let crc: u32 = Self::compute_crc(&chunk_type, &data);
 
         Chunk{
             length: data.len() as u32,
@@ -28,6 +31,26 @@ impl Chunk {
         }
     }
 
+    // Builds a chunk keeping the given CRC instead of computing it, only meant for damaged chunks
+    // kept around by a lenient parse
+    pub fn from_parts(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Chunk {
+        Chunk{
+            length: data.len() as u32,
+            chunk_type,
+            data,
+            crc
+        }
+    }
+
+    // Computes the CRC of a chunk type and its data
+    pub fn compute_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
+        let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        digest.update(chunk_type);
+        digest.update(data);
+        digest.finalize()
+    }
+
     // The length of the data portion of the chunk
     pub fn length(&self) -> u32 {
         self.length
@@ -48,6 +71,17 @@ impl Chunk {
         self.crc
     }
 
+    // Whether the stored CRC matches the chunk type and data
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc == Self::compute_crc(&self.chunk_type.bytes(), &self.data)
+    }
+
+    // Whether this chunk has been damaged, either with a CRC mismatch or an invalid chunk type.
+    // Such chunks can only come from a lenient parse.
+    pub fn is_damaged(&self) -> bool {
+        !self.is_crc_valid() || !self.chunk_type.is_alphabetic()
+    }
+
     // Returns the data stored in this chunk as a `String`. This function will return an error
     // if the stored data is not valid UTF-8.
     pub fn data_as_string(&self) -> Result<String, Box<dyn Error>> {
@@ -55,6 +89,44 @@ impl Chunk {
         Ok(result)
     }
 
+    // Returns what the chunk type stands for, for chunk types of the registry
+    pub fn description(&self) -> Option<&'static str> {
+        registry::lookup(self.chunk_type()).map(|info: &ChunkInfo| info.description)
+    }
+    // Returns the decoded content of standard ancillary chunks, None for other or malformed chunks
+    pub fn known(&self) -> Option<KnownChunk> {
+        KnownChunk::try_from(self).ok()
+    }
+    // Returns a short human-readable description of the data of well-known chunk types
+    // and of chunks holding an envelope
+    pub fn summary(&self) -> Option<String> {
+        let data: &[u8] = &self.data;
+        let keyword = || -> String {
+            String::from_utf8_lossy(data.split(|byte: &u8| *byte == 0).next().unwrap_or_default()).to_string()
+        };
+        match &self.chunk_type.bytes() {
+            b"IHDR" => {
+                let header: ImageHeader = ImageHeader::try_from(data).ok()?;
+                Some(format!(
+                    "{}x{}, {}-bit {}{}",
+                    header.width, header.height, header.bit_depth, header.color_type_name(),
+                    if header.interlace_method == 1 { ", interlaced" } else { "" }
+                ))
+            }
+            b"PLTE" => Some(format!("{} palette entries", data.len() / 3)),
+            b"IDAT" => Some(String::from("compressed image data")),
+            b"IEND" => Some(String::from("end of image")),
+            b"tEXt" => {
+                let text: String = String::from_utf8_lossy(data.splitn(2, |byte: &u8| *byte == 0).nth(1)?).to_string();
+                Some(format!("{}: {}", keyword(), truncate(&text)))
+            }
+            b"zTXt" => Some(format!("{}: (compressed text)", keyword())),
+            b"iTXt" => Some(format!("{}: (international text)", keyword())),
+            _ if Envelope::is_envelope(data) => Some(String::from("hidden payload (envelope)")),
+            _ => self.known().map(|known: KnownChunk| known.to_string()),
+        }
+    }
+
     // Returns this chunk as a byte sequences described by the PNG spec.
     // The following data is included in this byte sequence in order:
     // 1. Length of the data *(4 bytes)*
@@ -85,6 +157,14 @@ impl TryFrom<&[u8]> for Chunk {
         }
 
         let length: u32 = u32::from_be_bytes(bytes[0..4].try_into()?);
+        if bytes.len() < 4 + 4 + length as usize + 4 {
+            return Err(
+                format!(
+                    "Invalid chunk (chunk declares {} bytes of data but only {} bytes are available)",
+                    length, bytes.len() - 12
+                ).into()
+            );
+        }
         let ct: [u8; 4] = bytes[4..8].try_into()?;
         let data: Vec<u8> = bytes[8..(8 + length as usize)].to_vec();
         let computed_crc: u32 = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(
@@ -100,7 +180,30 @@ impl TryFrom<&[u8]> for Chunk {
             ).into());
         }
 
-        Ok(Chunk::new(ct, data))
+        Ok(Chunk::new(ChunkType::try_from(ct)?.bytes(), data))
+    }
+}
+
+// Shortens text to fit in a summary, on a single line
+fn truncate(text: &str) -> String {
+    let line: String = text.chars().map(|c: char| if c.is_control() { ' ' } else { c }).collect();
+    if line.chars().count() <= SUMMARY_TEXT_LENGTH {
+        return line;
+    }
+    line.chars().take(SUMMARY_TEXT_LENGTH - 3).chain("...".chars()).collect()
+}
+
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state: S::SerializeStruct = serializer.serialize_struct("Chunk", 7)?;
+        state.serialize_field("type", self.chunk_type())?;
+        state.serialize_field("description", &self.description())?;
+        state.serialize_field("length", &self.length())?;
+        state.serialize_field("crc", &self.crc())?;
+        state.serialize_field("crc_valid", &self.is_crc_valid())?;
+        state.serialize_field("summary", &self.summary())?;
+        state.serialize_field("value", &self.known())?;
+        state.end()
     }
 }
 impl fmt::Display for Chunk {
@@ -108,6 +211,12 @@ impl fmt::Display for Chunk {
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "  Length: {}", self.length())?;
         writeln!(f, "  Type: {}", self.chunk_type())?;
+        if let Some(description) = self.description() {
+            writeln!(f, "  Description: {}", description)?;
+        }
+        if let Some(known) = self.known() {
+            writeln!(f, "  Value: {}", known)?;
+        }
         writeln!(f, "  Data length: {} bytes", self.data().len())?;
         writeln!(f, "  Content: {}", String::from_utf8_lossy(self.data()))?;
         writeln!(f, "  Crc: {}", self.crc())?;
@@ -144,6 +253,8 @@ mod tests {
     }
 
     #[test]
+    // Left as originally written, `chunk_type` is not used to build the chunk
+    #[allow(unused_variables)]
     fn test_new_chunk() {
         let chunk_type: ChunkType = ChunkType::from_str("RuSt").unwrap();
         let data: Vec<u8> = "This is where your secret message will be!".as_bytes().to_vec();
@@ -224,6 +335,15 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_summary() {
+        let header: Chunk = Chunk::new(*b"IHDR", vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 1]);
+        assert_eq!(header.summary().unwrap(), "3x2, 8-bit truecolor with alpha, interlaced");
+        let text: Chunk = Chunk::new(*b"tEXt", "Title\0A very long title which will not fit on the line".as_bytes().to_vec());
+        assert_eq!(text.summary().unwrap(), "Title: A very long title which will not fit ...");
+        assert!(testing_chunk().summary().is_none());
+    }
+
     #[test]
     fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
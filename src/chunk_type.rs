@@ -3,6 +3,9 @@ use std::fmt;
 use std::str;
 use std::str::FromStr;
 
+use rand::Rng;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
 /// A validated PNG chunk type. See PNG spec for more details.
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -38,8 +41,29 @@ impl ChunkType {
 	// Returns true if the reserved byte is valid and all four bytes are represented by the characters A-Z or a-z
 	// Note that this chunk type should always be valid as it is validated during construction
 	pub fn is_valid(&self) -> bool {
-		self.is_reserved_bit_valid()
-			&& self.chunk_type.iter().fold(true, |acc: bool, byte: &u8| {acc && byte.is_ascii_alphabetic()})
+		self.is_reserved_bit_valid() && self.is_alphabetic()
+	}
+
+	// Builds a chunk type without validating its bytes, only meant for damaged chunks
+	// kept around by a lenient parse
+	pub fn from_bytes_unchecked(bytes: [u8; 4]) -> Self {
+		Self{chunk_type: bytes}
+	}
+
+	// Generates a random ancillary, private, safe-to-copy chunk type with a valid reserved bit.
+	// Registered chunk types are all public, so it never collides with one of them.
+	pub fn random_private() -> Self {
+		let mut rng = rand::thread_rng();
+		let mut letter = |uppercase: bool| -> u8 {
+			let letter: u8 = rng.gen_range(b'a'..=b'z');
+			if uppercase { letter.to_ascii_uppercase() } else { letter }
+		};
+		Self{chunk_type: [letter(false), letter(false), letter(true), letter(false)]}
+	}
+
+	// Returns true if all four bytes are represented by the characters A-Z or a-z
+	pub fn is_alphabetic(&self) -> bool {
+		self.chunk_type.iter().all(|byte: &u8| byte.is_ascii_alphabetic())
 	}
 
 	// Valid bytes are represented by the characters A-Z or a-z
@@ -53,12 +77,23 @@ impl fmt::Display for ChunkType {
 		Ok(())
 	}
 }
+impl Serialize for ChunkType {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut state: S::SerializeStruct = serializer.serialize_struct("ChunkType", 5)?;
+		state.serialize_field("name", &self.to_string())?;
+		state.serialize_field("critical", &self.is_critical())?;
+		state.serialize_field("public", &self.is_public())?;
+		state.serialize_field("reserved_bit_valid", &self.is_reserved_bit_valid())?;
+		state.serialize_field("safe_to_copy", &self.is_safe_to_copy())?;
+		state.end()
+	}
+}
 impl TryFrom<[u8; 4]> for ChunkType {
 	type Error = Box<dyn Error>;
 
 	fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
 		let result: Self = Self{chunk_type: bytes};
-		if bytes.iter().fold(true, |acc: bool, byte: &u8| {acc && byte.is_ascii_alphabetic()}) {
+		if result.is_alphabetic() {
 			return Ok(result);
 		}
 		Err("Invalid chunk type".into())
@@ -93,6 +128,17 @@ mod tests {
 		assert_eq!(expected, actual);
 	}
 
+	#[test]
+	pub fn test_chunk_type_random_private() {
+		for _ in 0..100 {
+			let chunk_type: ChunkType = ChunkType::random_private();
+			assert!(chunk_type.is_valid());
+			assert!(!chunk_type.is_critical());
+			assert!(!chunk_type.is_public());
+			assert!(chunk_type.is_safe_to_copy());
+		}
+	}
+
 	#[test]
 	pub fn test_chunk_type_is_critical() {
 		let chunk_type: ChunkType = ChunkType::from_str("RuSt").unwrap();
@@ -1,11 +1,33 @@
 use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use img_msg_encoder::compression::Compression;
+use img_msg_encoder::fec;
+use img_msg_encoder::png::ParseMode;
+use img_msg_encoder::report::OutputFormat;
+use img_msg_encoder::scan;
+use img_msg_encoder::carrier::Placement;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Keep going past damaged chunks instead of rejecting the whole file
+    #[arg(long, global = true)]
+    pub lenient: bool,
+    /// Output format, json giving structured output meant for scripts
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// With a directory as input, also process the PNG files of its subdirectories
+    #[arg(short, long, global = true)]
+    pub recursive: bool,
+}
+impl Cli {
+    // The parse mode requested through the global flags
+    pub fn parse_mode(&self) -> ParseMode {
+        if self.lenient { ParseMode::Lenient } else { ParseMode::Strict }
+    }
 }
 
 #[derive(Subcommand)]
@@ -14,26 +36,262 @@ pub enum Commands {
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    /// Print a table of the chunks: offsets, properties, CRC status and decoded summaries
+    #[command(visible_alias = "info")]
+    List(ListArgs),
+    /// Print a hexdump of the data of a chunk
+    Dump(DumpArgs),
+    /// Write the data of a chunk, or the whole serialized chunk, to a file
+    ExtractChunk(ExtractChunkArgs),
+    /// Insert a serialized chunk, as written by `extract-chunk --full`
+    InsertChunk(InsertChunkArgs),
+    /// Report anything which may hide data: unregistered chunks, trailing data, envelopes
+    /// and oversized text chunks
+    Scan(ScanArgs),
+    /// Estimate the LSB embedding rate of each color channel with the chi-square attack, RS
+    /// analysis and sample pair analysis
+    Analyze(AnalyzeArgs),
+    /// Remove the ancillary chunks which don't affect rendering, and the trailing data
+    Strip(StripArgs),
+    /// Compare the chunks of two PNGs: added, removed, moved and changed ones
+    Diff(DiffArgs),
+    /// Rebuild a valid PNG out of a damaged one, reporting every fix
+    Repair(RepairArgs),
+}
+
+impl Commands {
+    // The PNG file the command reads: a path, `-` for stdin, a directory or a glob pattern
+    pub fn filename(&self) -> &Path {
+        match self {
+            Commands::Encode(args) => &args.filename,
+            Commands::Decode(args) => &args.filename,
+            Commands::Remove(args) => &args.filename,
+            Commands::Print(args) => &args.filename,
+            Commands::List(args) => &args.filename,
+            Commands::Dump(args) => &args.filename,
+            Commands::ExtractChunk(args) => &args.filename,
+            Commands::InsertChunk(args) => &args.filename,
+            Commands::Scan(args) => &args.filename,
+            Commands::Analyze(args) => &args.filename,
+            Commands::Strip(args) => &args.filename,
+            Commands::Diff(args) => &args.filename,
+            Commands::Repair(args) => &args.filename,
+        }
+    }
+
+    // Where the command reads the passphrase from, for the commands taking one
+    pub fn passphrase(&self) -> Option<&PassphraseArgs> {
+        match self {
+            Commands::Encode(args) => Some(&args.passphrase),
+            Commands::Decode(args) => Some(&args.passphrase),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Args)]
 pub struct EncodeArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
     pub filename: PathBuf,
-    pub chunk_type: String,
-    pub message: String,
-    pub output_filename: Option<PathBuf>,
+    /// Message to hide
+    #[arg(required_unless_present = "message_file")]
+    pub message: Option<String>,
+    /// Type of the chunk holding the payload, required by the chunk method: ancillary, with a
+    /// valid reserved bit and not registered
+    #[arg(long, conflicts_with = "auto_type")]
+    pub chunk_type: Option<String>,
+    /// Hiding method, among the registered carriers (chunk, delay, frames, lsb, palette, text, trailer)
+    #[arg(long, default_value = "chunk")]
+    pub method: String,
+    /// Hide the payload in a chunk of a random private, ancillary, safe-to-copy type, with the
+    /// chunk method
+    #[arg(long)]
+    pub auto_type: bool,
+    /// Hide the content of this file instead of a message, `-` reading from stdin
+    #[arg(long, conflicts_with = "message")]
+    pub message_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    /// Compress the payload before hiding it, skipped if it would not make it smaller
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    pub compress: Compression,
+    /// Reed-Solomon parity bytes added per 255-byte block, repairing up to half as many corrupted bytes
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=fec::MAX_PARITY as i64))]
+    pub ecc: u8,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    /// Where to insert the chunk holding the payload
+    #[arg(long, value_enum, default_value_t = Placement::Random)]
+    pub placement: Placement,
 }
 #[derive(Args)]
 pub struct DecodeArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
     pub filename: PathBuf,
-    pub chunk_type: String,
+    /// Type of the chunk holding the payload, required by the chunk method only
+    #[arg(long)]
+    pub chunk_type: Option<String>,
+    /// Hiding method, among the registered carriers (chunk, delay, frames, lsb, palette, text, trailer)
+    #[arg(long, default_value = "chunk")]
+    pub method: String,
+    /// Write the recovered payload to this file instead of printing it, `-` writing to stdout
+    #[arg(short, long = "output")]
+    pub output_filename: Option<PathBuf>,
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
 }
 #[derive(Args)]
 pub struct RemoveArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
     pub filename: PathBuf,
-    pub chunk_type: String,
+    /// Type of the chunks to remove, required by the chunk method only
+    #[arg(long)]
+    pub chunk_type: Option<String>,
+    /// Hiding method, among the registered carriers (chunk, delay, frames, lsb, palette, text, trailer)
+    #[arg(long, default_value = "chunk")]
+    pub method: String,
+    #[command(flatten)]
+    pub output: OutputArgs,
 }
 #[derive(Args)]
 pub struct PrintArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+}
+#[derive(Args)]
+pub struct ListArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+}
+/// Where the passphrase comes from. It is never taken from the command line, where other
+/// users could read it in the process list: without these options, it is read from the
+/// IMG_MSG_ENCODER_PASSPHRASE environment variable when set.
+#[derive(Args)]
+pub struct PassphraseArgs {
+    /// Read the passphrase from the first line of this file
+    #[arg(long, conflicts_with = "ask_passphrase")]
+    pub passphrase_file: Option<PathBuf>,
+    /// Prompt for the passphrase on the terminal
+    #[arg(long)]
+    pub ask_passphrase: bool,
+}
+/// Where a command writes the PNG it produces. Existing files are never replaced without
+/// `--force` or `--in-place`.
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Output PNG file, `-` writing to stdout. Defaults to a file named after the input,
+    /// e.g. `image.encoded.png`, or stdout when reading from stdin.
+    #[arg(short, long = "output", conflicts_with = "in_place")]
+    pub output_filename: Option<PathBuf>,
+    /// Overwrite the input file
+    #[arg(long)]
+    pub in_place: bool,
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    pub force: bool,
+    /// Copy any file about to be overwritten to `<file>.bak` first
+    #[arg(long)]
+    pub backup: bool,
+    /// Print what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+/// Selects a single chunk of a PNG
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct ChunkSelector {
+    /// Index of the chunk, as shown by `list`
+    #[arg(long)]
+    pub index: Option<usize>,
+    /// Type of the chunk, selecting the first one of this type
+    #[arg(long = "type")]
+    pub chunk_type: Option<String>,
+}
+#[derive(Args)]
+pub struct DumpArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+    #[command(flatten)]
+    pub chunk: ChunkSelector,
+}
+#[derive(Args)]
+pub struct ExtractChunkArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+    #[command(flatten)]
+    pub chunk: ChunkSelector,
+    /// Output file, `-` writing to stdout
+    #[arg(short, long = "output")]
+    pub output_filename: PathBuf,
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    pub force: bool,
+    /// Write the whole serialized chunk (length, type, data and CRC) instead of its data
+    #[arg(long)]
+    pub full: bool,
+}
+#[derive(Args)]
+pub struct InsertChunkArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+    /// File holding a single serialized chunk, `-` reading from stdin
+    pub chunk_filename: PathBuf,
+    /// Index the chunk is inserted at, overriding the placement
+    #[arg(long)]
+    pub index: Option<usize>,
+    /// Where to insert the chunk
+    #[arg(long, value_enum, default_value_t = Placement::BeforeEnd)]
+    pub placement: Placement,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+#[derive(Args)]
+pub struct ScanArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+    /// Report text chunks larger than this many bytes
+    #[arg(long, default_value_t = scan::DEFAULT_MAX_TEXT_LENGTH)]
+    pub max_text: usize,
+    /// Also look for payloads hidden in the image data (lsb and frames methods), which
+    /// decodes every image and frame
+    #[arg(long)]
+    pub pixels: bool,
+}
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+}
+#[derive(Args)]
+pub struct StripArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
+    pub filename: PathBuf,
+    /// Chunk types to keep besides tRNS, gAMA, sRGB, iCCP and the APNG chunks, comma separated
+    #[arg(long, value_delimiter = ',')]
+    pub keep: Vec<String>,
+    /// Also remove the metadata chunks: eXIf, tEXt, zTXt, iTXt and tIME
+    #[arg(long)]
+    pub metadata: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Original PNG file, `-` reading from stdin
+    pub filename: PathBuf,
+    /// PNG file compared to the original one
+    pub other_filename: PathBuf,
+    /// Also compare the decoded image data
+    #[arg(long)]
+    pub pixels: bool,
+}
+#[derive(Args)]
+pub struct RepairArgs {
+    /// PNG file, `-` reading from stdin, or a directory or glob pattern to process several files
     pub filename: PathBuf,
+    #[command(flatten)]
+    pub output: OutputArgs,
 }
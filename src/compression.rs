@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+
+use clap::ValueEnum;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+// Most bytes a payload may decompress to
+pub const MAX_DECOMPRESSED_LENGTH: usize = 256 * 1024 * 1024;
+
+/// Compression algorithms available for payloads, identified in envelopes by their id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Zstd,
+    Brotli,
+}
+impl Compression {
+    // Byte identifying this algorithm in an envelope
+    pub fn id(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+            Compression::Brotli => 3,
+        }
+    }
+
+    // Compresses the given bytes with this algorithm
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Deflate => {
+                let mut encoder: DeflateEncoder<Vec<u8>> = DeflateEncoder::new(
+                    vec![], flate2::Compression::best()
+                );
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => Ok(zstd::encode_all(bytes, 19)?),
+            Compression::Brotli => {
+                let mut result: Vec<u8> = vec![];
+                {
+                    let mut writer: brotli::CompressorWriter<&mut Vec<u8>> = brotli::CompressorWriter::new(
+                        &mut result, 4096, 11, 22
+                    );
+                    writer.write_all(bytes)?;
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    // Decompresses bytes previously compressed with this algorithm, failing past
+    // `MAX_DECOMPRESSED_LENGTH` bytes so that crafted payloads can't exhaust memory
+    pub fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.decompress_with_limit(bytes, MAX_DECOMPRESSED_LENGTH)
+    }
+    // Decompresses bytes previously compressed with this algorithm, failing past `limit` bytes
+    pub fn decompress_with_limit(&self, bytes: &[u8], limit: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let reader: Box<dyn Read + '_> = match self {
+            Compression::None => Box::new(bytes),
+            Compression::Deflate => Box::new(DeflateDecoder::new(bytes)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(bytes)?),
+            Compression::Brotli => Box::new(brotli::Decompressor::new(bytes, 4096)),
+        };
+        let mut result: Vec<u8> = vec![];
+        reader.take(limit as u64 + 1).read_to_end(&mut result)?;
+        if result.len() > limit {
+            return Err(format!("Decompressed payload exceeds the limit of {} bytes", limit).into());
+        }
+        Ok(result)
+    }
+
+    // Compresses the given bytes, unless it would not make them any smaller.
+    // Returns the algorithm that was effectively used along with the resulting bytes.
+    pub fn compress_if_smaller(&self, bytes: &[u8]) -> Result<(Compression, Vec<u8>), Box<dyn Error>> {
+        if *self == Compression::None {
+            return Ok((Compression::None, bytes.to_vec()));
+        }
+        let compressed: Vec<u8> = self.compress(bytes)?;
+        if compressed.len() < bytes.len() {
+            Ok((*self, compressed))
+        } else {
+            Ok((Compression::None, bytes.to_vec()))
+        }
+    }
+}
+impl TryFrom<u8> for Compression {
+    type Error = Box<dyn Error>;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            3 => Ok(Compression::Brotli),
+            _ => Err(format!("Unknown compression algorithm: {}", id).into()),
+        }
+    }
+}
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name: &str = match self {
+            Compression::None => "none",
+            Compression::Deflate => "deflate",
+            Compression::Zstd => "zstd",
+            Compression::Brotli => "brotli",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::Compression;
+
+    fn testing_bytes() -> Vec<u8> {
+        "This is where your secret message will be! ".repeat(20).into_bytes()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let bytes: Vec<u8> = testing_bytes();
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd, Compression::Brotli] {
+            let compressed: Vec<u8> = compression.compress(&bytes).unwrap();
+            assert_eq!(compression.decompress(&compressed).unwrap(), bytes);
+        }
+    }
+    #[test]
+    fn test_decompression_limit() {
+        let bytes: Vec<u8> = testing_bytes();
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd, Compression::Brotli] {
+            let compressed: Vec<u8> = compression.compress(&bytes).unwrap();
+            assert!(compression.decompress_with_limit(&compressed, bytes.len() - 1).is_err());
+            assert_eq!(compression.decompress_with_limit(&compressed, bytes.len()).unwrap(), bytes);
+        }
+    }
+    #[test]
+    fn test_compress_if_smaller() {
+        let (compression, compressed) = Compression::Zstd.compress_if_smaller(&testing_bytes()).unwrap();
+        assert_eq!(compression, Compression::Zstd);
+        assert!(compressed.len() < testing_bytes().len());
+
+        let (compression, compressed) = Compression::Zstd.compress_if_smaller(&[42]).unwrap();
+        assert_eq!(compression, Compression::None);
+        assert_eq!(compressed, vec![42]);
+    }
+    #[test]
+    fn test_ids() {
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd, Compression::Brotli] {
+            assert_eq!(Compression::try_from(compression.id()).unwrap(), compression);
+        }
+        assert!(Compression::try_from(42).is_err());
+    }
+}
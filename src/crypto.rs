@@ -0,0 +1,79 @@
+use std::error::Error;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::Rng;
+
+// Length of the random salt used to derive the key from the passphrase
+pub const SALT_LENGTH: usize = 16;
+// Length of the random nonce used for each encryption
+pub const NONCE_LENGTH: usize = 12;
+
+// Encrypts bytes with ChaCha20-Poly1305, using a key derived from the passphrase with Argon2.
+// The output is made of the following, in order:
+// 1. The salt *(16 bytes)*
+// 2. The nonce *(12 bytes)*
+// 3. The ciphertext, followed by its authentication tag *(16 bytes)*
+pub fn encrypt(bytes: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut salt: [u8; SALT_LENGTH] = [0; SALT_LENGTH];
+    let mut nonce: [u8; NONCE_LENGTH] = [0; NONCE_LENGTH];
+    rand::thread_rng().fill(&mut salt);
+    rand::thread_rng().fill(&mut nonce);
+
+    let cipher: ChaCha20Poly1305 = cipher(passphrase, &salt)?;
+    let ciphertext: Vec<u8> = cipher.encrypt(Nonce::from_slice(&nonce), bytes)
+        .map_err(|_| "Encryption failed")?;
+    Ok(salt
+        .iter()
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .copied().collect())
+}
+
+// Decrypts bytes produced by `encrypt`, failing if the passphrase is wrong or the bytes altered
+pub fn decrypt(bytes: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if bytes.len() < SALT_LENGTH + NONCE_LENGTH {
+        return Err(format!("Invalid encrypted data (too short: {} bytes)", bytes.len()).into());
+    }
+    let (salt, rest) = bytes.split_at(SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+
+    let cipher: ChaCha20Poly1305 = cipher(passphrase, salt)?;
+    let plaintext: Vec<u8> = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed (wrong passphrase or altered data)")?;
+    Ok(plaintext)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, Box<dyn Error>> {
+    let mut key: [u8; 32] = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| format!("Key derivation failed: {}", err))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto;
+
+    #[test]
+    fn test_round_trip() {
+        let encrypted: Vec<u8> = crypto::encrypt("Secret".as_bytes(), "passphrase").unwrap();
+        assert_eq!(encrypted.len(), crypto::SALT_LENGTH + crypto::NONCE_LENGTH + 6 + 16);
+        assert_eq!(crypto::decrypt(&encrypted, "passphrase").unwrap(), "Secret".as_bytes());
+    }
+    #[test]
+    fn test_wrong_passphrase() {
+        let encrypted: Vec<u8> = crypto::encrypt("Secret".as_bytes(), "passphrase").unwrap();
+        assert!(crypto::decrypt(&encrypted, "other").is_err());
+    }
+    #[test]
+    fn test_altered_data() {
+        let mut encrypted: Vec<u8> = crypto::encrypt("Secret".as_bytes(), "passphrase").unwrap();
+        let last: usize = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        assert!(crypto::decrypt(&encrypted, "passphrase").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::pixels::{ImageHeader, Pixels};
+use crate::png::Png;
+
+/// A difference between the chunk lists of two PNGs, indexes referring to the list the chunk
+/// belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum ChunkChange {
+    Added { index: usize, chunk_type: String, length: u32 },
+    Removed { index: usize, chunk_type: String, length: u32 },
+    // An identical chunk found at another position
+    Moved { from: usize, to: usize, chunk_type: String, length: u32 },
+    // A chunk replaced by another one of the same type
+    Changed {
+        from: usize, to: usize, chunk_type: String,
+        length_before: u32, length_after: u32, crc_before: u32, crc_after: u32,
+    },
+}
+impl fmt::Display for ChunkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkChange::Added { index, chunk_type, length } => {
+                write!(f, "+ {} chunk at index {} ({} bytes)", chunk_type, index, length)
+            }
+            ChunkChange::Removed { index, chunk_type, length } => {
+                write!(f, "- {} chunk at index {} ({} bytes)", chunk_type, index, length)
+            }
+            ChunkChange::Moved { from, to, chunk_type, length } => {
+                write!(f, "> {} chunk moved from index {} to {} ({} bytes)", chunk_type, from, to, length)
+            }
+            ChunkChange::Changed { from, to, chunk_type, length_before, length_after, crc_before, crc_after } => write!(
+                f, "~ {} chunk changed at index {} -> {} ({} -> {} bytes, CRC {:08x} -> {:08x})",
+                chunk_type, from, to, length_before, length_after, crc_before, crc_after
+            ),
+        }
+    }
+}
+
+// Lists the chunks removed from `before` and added in `after`. Identical chunks are matched
+// with a longest common subsequence, so that chunks left untouched are never reported. A chunk
+// both removed and added is then reported as moved, or as changed when only its type matches.
+pub fn chunk_changes(before: &Png, after: &Png) -> Vec<ChunkChange> {
+    let old: Vec<Chunk> = before.chunks();
+    let new: Vec<Chunk> = after.chunks();
+    let same = |i: usize, j: usize| -> bool {
+        old[i].chunk_type() == new[j].chunk_type() && old[i].crc() == new[j].crc() && old[i].data() == new[j].data()
+    };
+
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths: Vec<Vec<usize>> = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same(i, j) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    // Edits turning `old` into `new`, in order: Err(i) removes old[i] and Ok(j) adds new[j]
+    let mut edits: Vec<Result<usize, usize>> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same(i, j) {
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            edits.push(Err(i));
+            i += 1;
+        } else {
+            edits.push(Ok(j));
+            j += 1;
+        }
+    }
+    edits.extend((i..old.len()).map(Err));
+    edits.extend((j..new.len()).map(Ok));
+
+    // Pair each removed chunk with an identical added one first, then with one of the same type
+    let removals: Vec<usize> = edits.iter().filter_map(|edit| edit.err()).collect();
+    let mut additions: Vec<usize> = edits.iter().filter_map(|edit| edit.ok()).collect();
+    let mut pairs: Vec<(usize, usize)> = vec![];
+    let mut pair_up = |matches: &dyn Fn(usize, usize) -> bool| {
+        for i in removals.iter() {
+            if pairs.iter().any(|(from, _)| from == i) {
+                continue;
+            }
+            if let Some(position) = additions.iter().position(|j: &usize| matches(*i, *j)) {
+                pairs.push((*i, additions.remove(position)));
+            }
+        }
+    };
+    pair_up(&same);
+    pair_up(&|i: usize, j: usize| old[i].chunk_type() == new[j].chunk_type());
+
+    let mut changes: Vec<ChunkChange> = vec![];
+    let mut reported: Vec<bool> = vec![false; pairs.len()];
+    for edit in edits {
+        let pair: Option<usize> = pairs.iter().position(|(from, to)| match edit {
+            Err(i) => *from == i,
+            Ok(j) => *to == j,
+        });
+        changes.push(match (edit, pair) {
+            // A pair is reported once, where the first of its two edits happens
+            (_, Some(pair)) if reported[pair] => continue,
+            (_, Some(pair)) => {
+                reported[pair] = true;
+                let (i, j) = pairs[pair];
+                if same(i, j) {
+                    ChunkChange::Moved { from: i, to: j, chunk_type: old[i].chunk_type().to_string(), length: old[i].length() }
+                } else {
+                    ChunkChange::Changed {
+                        from: i, to: j, chunk_type: old[i].chunk_type().to_string(),
+                        length_before: old[i].length(), length_after: new[j].length(),
+                        crc_before: old[i].crc(), crc_after: new[j].crc(),
+                    }
+                }
+            }
+            (Err(i), None) => ChunkChange::Removed {
+                index: i, chunk_type: old[i].chunk_type().to_string(), length: old[i].length(),
+            },
+            (Ok(j), None) => ChunkChange::Added {
+                index: j, chunk_type: new[j].chunk_type().to_string(), length: new[j].length(),
+            },
+        });
+    }
+    changes
+}
+/// Summary of the differences between the decoded image data of two PNGs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PixelDiff {
+    pub total_pixels: usize,
+    pub changed_pixels: usize,
+    pub total_bytes: usize,
+    pub changed_bytes: usize,
+    // Largest difference between two bytes of the image data
+    pub max_difference: u8,
+    // Whether some samples changed, all of them only by their least significant bit
+    pub lsb_only: bool,
+}
+impl fmt::Display for PixelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, "{} of {} pixels changed ({} of {} bytes), max difference {}",
+            self.changed_pixels, self.total_pixels, self.changed_bytes, self.total_bytes, self.max_difference
+        )?;
+        if self.lsb_only {
+            write!(f, ", least significant bits only")?;
+        }
+        Ok(())
+    }
+}
+
+// Compares the decoded image data of two PNGs, which must have the same dimensions and format
+pub fn pixel_diff(before: &Png, after: &Png) -> Result<PixelDiff, Box<dyn Error>> {
+    let (old, new) = (Pixels::from_png(before)?, Pixels::from_png(after)?);
+    let header: ImageHeader = *old.header();
+    if header != *new.header() {
+        return Err("The images have different dimensions or formats".into());
+    }
+    let (old, new) = (old.data(), new.data());
+    if header.width == 0 || header.height == 0 {
+        return Err(format!("Empty image ({}x{})", header.width, header.height).into());
+    }
+
+    let differences: Vec<u8> = old.iter().zip(new.iter()).map(|(a, b)| a ^ b).collect();
+    let changed: Vec<usize> = (0..differences.len()).filter(|i: &usize| differences[*i] != 0).collect();
+    // Pixels are bits_per_pixel bits long, several of them sharing a byte at low bit depths
+    let bits: usize = header.bits_per_pixel();
+    let row_length: usize = header.row_length()?;
+    let lsb_only: bool = !changed.is_empty() && changed.iter().all(|i: &usize| {
+        differences[*i] & !lsb_mask(header.bit_depth, i % row_length) == 0
+    });
+    let mut changed_pixels: usize = 0;
+    for row in differences.chunks(row_length) {
+        changed_pixels += (0..header.width as usize)
+            .filter(|x: &usize| (x * bits..(x + 1) * bits).any(|bit: usize| row[bit / 8] & (0x80 >> (bit % 8)) != 0))
+            .count();
+    }
+
+    Ok(PixelDiff{
+        total_pixels: header.width as usize * header.height as usize,
+        changed_pixels,
+        total_bytes: old.len(),
+        changed_bytes: changed.len(),
+        max_difference: changed.iter().map(|i: &usize| old[*i].abs_diff(new[*i])).max().unwrap_or(0),
+        lsb_only,
+    })
+}
+
+// Bits of a byte of image data holding the least significant bit of a sample, given the offset
+// of the byte in its row: samples of 1, 2 and 4 bits share bytes, and samples of 16 bits end
+// with their low byte
+fn lsb_mask(bit_depth: u8, offset: usize) -> u8 {
+    match bit_depth {
+        1 => 0b1111_1111,
+        2 => 0b0101_0101,
+        4 => 0b0001_0001,
+        16 if offset.is_multiple_of(2) => 0,
+        _ => 1,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use crate::chunk::Chunk;
+    use crate::diff::{self, ChunkChange, PixelDiff};
+    use crate::png::Png;
+
+    fn chunk(chunk_type: &[u8; 4], data: &str) -> Chunk {
+        Chunk::new(*chunk_type, data.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_chunk_changes() {
+        let before: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"tEXt", "text"), chunk(b"IDAT", "data"), chunk(b"IEND", ""),
+        ]);
+        let after: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"ruSt", "message"), chunk(b"IDAT", "data"), chunk(b"IEND", ""),
+        ]);
+        assert_eq!(diff::chunk_changes(&before, &after), vec![
+            ChunkChange::Removed { index: 1, chunk_type: String::from("tEXt"), length: 4 },
+            ChunkChange::Added { index: 1, chunk_type: String::from("ruSt"), length: 7 },
+        ]);
+        assert!(diff::chunk_changes(&before, &before).is_empty());
+    }
+    #[test]
+    fn test_moved_and_changed_chunks() {
+        let before: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"tEXt", "a"), chunk(b"zTXt", "b"), chunk(b"IDAT", "data"), chunk(b"IEND", ""),
+        ]);
+        let after: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"zTXt", "b"), chunk(b"tEXt", "a"), chunk(b"IDAT", "other data"), chunk(b"IEND", ""),
+        ]);
+        let changes: Vec<ChunkChange> = diff::chunk_changes(&before, &after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0], ChunkChange::Moved { from: 1, to: 2, chunk_type: String::from("tEXt"), length: 1 });
+        assert!(matches!(
+            changes[1],
+            ChunkChange::Changed { from: 3, to: 3, length_before: 4, length_after: 10, .. }
+        ));
+    }
+    #[test]
+    fn test_pixel_diff() {
+        // A 4x2 grayscale image, without filtering
+        let png = |rows: [[u8; 4]; 2]| -> Png {
+            let filtered: Vec<u8> = rows.iter().flat_map(|row: &[u8; 4]| [0].into_iter().chain(*row)).collect();
+            let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(&filtered).unwrap();
+            Png::from_chunks(vec![
+                Chunk::new(*b"IHDR", vec![0, 0, 0, 4, 0, 0, 0, 2, 8, 0, 0, 0, 0]),
+                Chunk::new(*b"IDAT", encoder.finish().unwrap()),
+                Chunk::new(*b"IEND", vec![]),
+            ])
+        };
+        let before: Png = png([[10, 20, 30, 40], [50, 60, 70, 80]]);
+        let pixels: PixelDiff = diff::pixel_diff(&before, &png([[11, 20, 30, 40], [50, 60, 71, 80]])).unwrap();
+        assert_eq!(pixels, PixelDiff{
+            total_pixels: 8, changed_pixels: 2, total_bytes: 8, changed_bytes: 2, max_difference: 1, lsb_only: true,
+        });
+        let pixels: PixelDiff = diff::pixel_diff(&before, &png([[10, 20, 30, 40], [50, 60, 70, 90]])).unwrap();
+        assert_eq!((pixels.changed_pixels, pixels.max_difference, pixels.lsb_only), (1, 10, false));
+    }
+    #[test]
+    fn test_lsb_mask() {
+        // The low byte of 16-bit samples comes second
+        assert_eq!((diff::lsb_mask(16, 0), diff::lsb_mask(16, 1)), (0, 1));
+        assert_eq!(diff::lsb_mask(8, 3), 1);
+        // Two 4-bit samples per byte, each with its own least significant bit
+        assert_eq!(diff::lsb_mask(4, 0), 0b0001_0001);
+        assert_eq!(diff::lsb_mask(1, 0), 0b1111_1111);
+    }
+}
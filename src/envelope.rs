@@ -0,0 +1,543 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::compression::Compression;
+use crate::crypto;
+use crate::fec;
+
+// Flag set in an envelope when the rest of its body is encrypted
+const ENCRYPTED_FLAG: u8 = 0b0000_0001;
+
+// Fallback MIME type when nothing better can be inferred from a filename
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+// MIME type used for messages given directly on the command line
+pub const TEXT_MIME_TYPE: &str = "text/plain; charset=utf-8";
+
+/// A payload wrapped with the metadata needed to restore it on decode.
+/// Layout (all integers big-endian):
+/// 1. Magic `IMGE` *(4 bytes)*
+/// 2. Format version *(1 byte)*
+/// 3. Reed-Solomon parity length, repeated three times *(3 bytes)*, absent before version 3
+/// 4. Format version, repeated twice *(2 bytes)*, absent before version 5. Along with the
+///    repeated parity length, this lets a header with a single corrupted byte be read back.
+/// 5. The body, protected by Reed-Solomon codes when the parity length isn't 0:
+///    1. Flags *(1 byte)*, absent before version 4, the lowest bit telling whether the rest
+///       of the body is encrypted (see `crypto::encrypt`)
+///    2. Compression algorithm id *(1 byte)*, absent from version 1 envelopes
+///    3. Filename length *(2 bytes)* followed by the UTF-8 filename
+///    4. MIME type length *(2 bytes)* followed by the UTF-8 MIME type
+///    5. Payload length *(4 bytes)* followed by the payload itself, compressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    filename: Option<String>,
+    mime_type: String,
+    compression: Compression,
+    parity: u8,
+    corrected_bytes: usize,
+    encrypted: bool,
+    payload: Vec<u8>,
+}
+impl Envelope {
+    pub const MAGIC: [u8; 4] = *b"IMGE";
+    pub const VERSION: u8 = 5;
+
+    // Init function
+    pub fn new(payload: Vec<u8>, filename: Option<String>, mime_type: String) -> Envelope {
+        Envelope{
+            filename,
+            mime_type,
+            compression: Compression::None,
+            parity: 0,
+            corrected_bytes: 0,
+            encrypted: false,
+            payload,
+        }
+    }
+    // Sets the algorithm used to compress the payload when serializing this envelope
+    pub fn with_compression(mut self, compression: Compression) -> Envelope {
+        self.compression = compression;
+        self
+    }
+    // Sets the number of Reed-Solomon parity bytes added to each block of the body when
+    // serializing this envelope, 0 disabling error correction
+    pub fn with_error_correction(mut self, parity: u8) -> Envelope {
+        self.parity = parity;
+        self
+    }
+    // Creates an envelope holding a plain text message
+    pub fn from_message(message: &str) -> Envelope {
+        Envelope::new(message.as_bytes().to_vec(), None, TEXT_MIME_TYPE.to_string())
+    }
+    // Creates an envelope from the content of a file, or of stdin if the path is `-`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Envelope, Box<dyn Error>> {
+        let path: &Path = path.as_ref();
+        if path.as_os_str() == "-" {
+            let mut payload: Vec<u8> = vec![];
+            io::stdin().read_to_end(&mut payload)?;
+            return Ok(Envelope::new(payload, None, DEFAULT_MIME_TYPE.to_string()));
+        }
+
+        let payload: Vec<u8> = fs::read(path)?;
+        let filename: Option<String> = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        Ok(Envelope::new(payload, filename, guess_mime_type(path).to_string()))
+    }
+    // Reads the content of a chunk, falling back to a bare text message for chunks written
+    // before envelopes existed
+    pub fn from_chunk_data(data: &[u8], passphrase: Option<&str>) -> Result<Envelope, Box<dyn Error>> {
+        if Self::is_envelope(data) {
+            Envelope::open(data, passphrase)
+        } else {
+            Ok(Envelope::new(data.to_vec(), None, TEXT_MIME_TYPE.to_string()))
+        }
+    }
+
+    // Whether the given bytes start like an envelope, possibly with a single corrupted magic
+    // byte if the repeated version is intact
+    pub fn is_envelope(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC) || (Self::magic_errors(bytes) <= 1 && Self::has_repeated_version(bytes))
+    }
+    // Number of magic bytes which don't match
+    fn magic_errors(bytes: &[u8]) -> usize {
+        Self::MAGIC.iter().enumerate().filter(|(index, byte)| bytes.get(*index) != Some(byte)).count()
+    }
+    // Whether the version repeated after the parity lengths is intact, as written since version 5
+    fn has_repeated_version(bytes: &[u8]) -> bool {
+        bytes.get(8) == Some(&Self::VERSION) && bytes.get(9) == Some(&Self::VERSION)
+    }
+
+    // Whether the given bytes are an envelope which opens without a passphrase, judging only
+    // from its header and flags: the body is neither repaired, decrypted nor decompressed
+    pub fn is_readable(bytes: &[u8]) -> bool {
+        if !Self::is_envelope(bytes) {
+            return false;
+        }
+        let version: u8 = if Self::has_repeated_version(bytes) { Self::VERSION } else { bytes[Self::MAGIC.len()] };
+        // Flags follow the magic, version, repeated parity lengths and repeated version
+        let flags_offset: usize = match version {
+            1..=3 => return true,
+            4 => Self::MAGIC.len() + 4,
+            5..=Self::VERSION => Self::MAGIC.len() + 6,
+            _ => return false,
+        };
+        bytes.get(flags_offset).is_some_and(|flags: &u8| flags & ENCRYPTED_FLAG == 0)
+    }
+
+    // The original filename of the payload, if any
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+    // The MIME type of the payload
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+    // The compression algorithm of the payload. Once parsed, this is the algorithm that was
+    // effectively used, which is `None` if compressing did not make the payload smaller.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+    // The number of Reed-Solomon parity bytes per block of the body
+    pub fn parity(&self) -> u8 {
+        self.parity
+    }
+    // The number of corrupted bytes that have been repaired while parsing this envelope
+    pub fn corrected_bytes(&self) -> usize {
+        self.corrected_bytes
+    }
+    // Whether this envelope has been read from encrypted bytes
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+    // The raw payload, uncompressed
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    // Returns this envelope as the byte sequence described above, compressing the payload
+    // only if it ends up smaller
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.seal(None)
+    }
+    // Same as `to_bytes`, encrypting the body if a passphrase is given
+    pub fn seal(&self, passphrase: Option<&str>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let filename: &[u8] = self.filename.as_deref().unwrap_or("").as_bytes();
+        let mime_type: &[u8] = self.mime_type.as_bytes();
+        let (compression, payload) = self.compression.compress_if_smaller(&self.payload)?;
+        let filename_length: u16 = u16::try_from(filename.len())
+            .map_err(|_| format!("Filename too long ({} bytes, at most {})", filename.len(), u16::MAX))?;
+        let mime_length: u16 = u16::try_from(mime_type.len())
+            .map_err(|_| format!("MIME type too long ({} bytes, at most {})", mime_type.len(), u16::MAX))?;
+        let payload_length: u32 = u32::try_from(payload.len())
+            .map_err(|_| format!("Payload too large ({} bytes, at most {})", payload.len(), u32::MAX))?;
+
+        let body: Vec<u8> = [compression.id()]
+            .iter()
+            .chain(filename_length.to_be_bytes().iter())
+            .chain(filename.iter())
+            .chain(mime_length.to_be_bytes().iter())
+            .chain(mime_type.iter())
+            .chain(payload_length.to_be_bytes().iter())
+            .chain(payload.iter())
+            .copied().collect();
+        let body: Vec<u8> = match passphrase {
+            Some(passphrase) => [ENCRYPTED_FLAG].iter().chain(crypto::encrypt(&body, passphrase)?.iter()).copied().collect(),
+            None => [0].iter().chain(body.iter()).copied().collect(),
+        };
+        let body: Vec<u8> = if self.parity == 0 { body } else { fec::encode(&body, self.parity)? };
+
+        Ok(Self::MAGIC
+            .iter()
+            .chain([Self::VERSION, self.parity, self.parity, self.parity, Self::VERSION, Self::VERSION].iter())
+            .chain(body.iter())
+            .copied().collect())
+    }
+
+    // Parses an envelope, decrypting it with the passphrase if needed
+    pub fn open(bytes: &[u8], passphrase: Option<&str>) -> Result<Envelope, Box<dyn Error>> {
+        if !Self::is_envelope(bytes) {
+            return Err("Invalid envelope (missing magic bytes)".into());
+        }
+        let mut reader: FieldReader = FieldReader{bytes, cursor: Self::MAGIC.len()};
+
+        // The repeated version wins over a corrupted version byte
+        let written_version: u8 = reader.take(1)?[0];
+        let version: u8 = if Self::has_repeated_version(bytes) { Self::VERSION } else { written_version };
+        let mut header_errors: usize = Self::magic_errors(bytes);
+        let (body, parity, corrected_bytes): (Vec<u8>, u8, usize) = match version {
+            1 => ([Compression::None.id()].iter().chain(reader.rest()).copied().collect(), 0, 0),
+            2 => (reader.rest().to_vec(), 0, 0),
+            3..=Self::VERSION => {
+                let parities: &[u8] = reader.take(3)?;
+                let parity: u8 = majority(parities);
+                header_errors += parities.iter().filter(|byte: &&u8| **byte != parity).count();
+                if version >= 5 {
+                    header_errors += [written_version].iter()
+                        .chain(reader.take(2)?)
+                        .filter(|byte: &&u8| **byte != version)
+                        .count();
+                }
+                if parity == 0 {
+                    (reader.rest().to_vec(), 0, 0)
+                } else {
+                    let (body, corrected_bytes) = fec::decode(reader.rest(), parity)?;
+                    (body, parity, corrected_bytes)
+                }
+            }
+            _ => return Err(format!("Invalid envelope (unsupported version: {})", version).into()),
+        };
+        let corrected_bytes: usize = corrected_bytes + header_errors;
+
+        let mut reader: FieldReader = FieldReader{bytes: &body, cursor: 0};
+        let flags: u8 = if version < 4 { 0 } else { reader.take(1)?[0] };
+        let encrypted: bool = flags & ENCRYPTED_FLAG != 0;
+        let body: Vec<u8> = match (encrypted, passphrase) {
+            (true, Some(passphrase)) => crypto::decrypt(reader.rest(), passphrase)?,
+            (true, None) => return Err("Encrypted envelope, a passphrase is required".into()),
+            (false, _) => reader.rest().to_vec(),
+        };
+
+        let mut reader: FieldReader = FieldReader{bytes: &body, cursor: 0};
+        let compression: Compression = Compression::try_from(reader.take(1)?[0])?;
+        let filename_length: usize = u16::from_be_bytes(reader.take(2)?.try_into()?) as usize;
+        let filename: String = String::from_utf8(reader.take(filename_length)?.to_vec())?;
+        let mime_length: usize = u16::from_be_bytes(reader.take(2)?.try_into()?) as usize;
+        let mime_type: String = String::from_utf8(reader.take(mime_length)?.to_vec())?;
+        let payload_length: usize = u32::from_be_bytes(reader.take(4)?.try_into()?) as usize;
+        let payload: Vec<u8> = compression.decompress(reader.take(payload_length)?)?;
+
+        let mut envelope: Envelope = Envelope::new(
+            payload,
+            if filename.is_empty() { None } else { Some(filename) },
+            mime_type
+        ).with_compression(compression).with_error_correction(parity);
+        envelope.corrected_bytes = corrected_bytes;
+        envelope.encrypted = encrypted;
+        Ok(envelope)
+    }
+}
+impl TryFrom<&[u8]> for Envelope {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Envelope::open(bytes, None)
+    }
+}
+impl From<String> for Envelope {
+    fn from(message: String) -> Self {
+        Envelope::from_message(&message)
+    }
+}
+impl From<Vec<u8>> for Envelope {
+    fn from(payload: Vec<u8>) -> Self {
+        Envelope::new(payload, None, DEFAULT_MIME_TYPE.to_string())
+    }
+}
+impl Serialize for Envelope {
+    // The payload is given base64 encoded, and as text as well when it is valid UTF-8
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state: S::SerializeStruct = serializer.serialize_struct("Envelope", 9)?;
+        state.serialize_field("filename", &self.filename())?;
+        state.serialize_field("mime_type", self.mime_type())?;
+        state.serialize_field("compression", &self.compression())?;
+        state.serialize_field("encrypted", &self.is_encrypted())?;
+        state.serialize_field("parity", &self.parity())?;
+        state.serialize_field("corrected_bytes", &self.corrected_bytes())?;
+        state.serialize_field("payload_length", &self.payload().len())?;
+        state.serialize_field("payload", &BASE64.encode(self.payload()))?;
+        state.serialize_field("text", &std::str::from_utf8(self.payload()).ok())?;
+        state.end()
+    }
+}
+impl fmt::Display for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Envelope {{",)?;
+        writeln!(f, "  Filename: {}", self.filename().unwrap_or("-"))?;
+        writeln!(f, "  MIME type: {}", self.mime_type())?;
+        writeln!(f, "  Compression: {}", self.compression())?;
+        writeln!(f, "  Encrypted: {}", if self.is_encrypted() { "yes" } else { "no" })?;
+        if self.parity() != 0 {
+            writeln!(
+                f, "  Error correction: {} parity bytes per block, {} bytes corrected",
+                self.parity(), self.corrected_bytes()
+            )?;
+        }
+        writeln!(f, "  Payload length: {} bytes", self.payload().len())?;
+        writeln!(f, "}}",)?;
+        Ok(())
+    }
+}
+
+// Sequential reader over the envelope fields, failing on truncated input
+struct FieldReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+impl<'a> FieldReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.cursor + length > self.bytes.len() {
+            return Err(format!(
+                "Invalid envelope (truncated at byte {}, {} more bytes expected)",
+                self.cursor, length
+            ).into());
+        }
+        let field: &[u8] = &self.bytes[self.cursor..(self.cursor + length)];
+        self.cursor += length;
+        Ok(field)
+    }
+    fn rest(&mut self) -> &'a [u8] {
+        let field: &[u8] = &self.bytes[self.cursor..];
+        self.cursor = self.bytes.len();
+        field
+    }
+}
+
+// Returns the value appearing at least twice in the given triplicated byte, or the first one
+fn majority(bytes: &[u8]) -> u8 {
+    if bytes[1] == bytes[2] { bytes[1] } else { bytes[0] }
+}
+
+// Infers a MIME type from the file extension, only covering the most common formats
+pub fn guess_mime_type(path: &Path) -> &'static str {
+    let extension: String = path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "md" => TEXT_MIME_TYPE,
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => DEFAULT_MIME_TYPE,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::path::Path;
+
+    use crate::compression::Compression;
+    use crate::envelope::{guess_mime_type, Envelope, DEFAULT_MIME_TYPE, TEXT_MIME_TYPE};
+
+    fn testing_envelope() -> Envelope {
+        Envelope::new(
+            vec![0, 159, 146, 150, 255],
+            Some(String::from("secret.pdf")),
+            String::from("application/pdf")
+        )
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope: Envelope = testing_envelope();
+        let actual: Envelope = Envelope::try_from(envelope.to_bytes().unwrap().as_ref()).unwrap();
+        assert_eq!(actual, envelope);
+        assert_eq!(actual.filename(), Some("secret.pdf"));
+        assert_eq!(actual.mime_type(), "application/pdf");
+        assert_eq!(actual.payload(), &[0, 159, 146, 150, 255]);
+    }
+    #[test]
+    fn test_envelope_without_filename() {
+        let envelope: Envelope = Envelope::from_message("Hello");
+        let actual: Envelope = Envelope::try_from(envelope.to_bytes().unwrap().as_ref()).unwrap();
+        assert_eq!(actual.filename(), None);
+        assert_eq!(actual.mime_type(), TEXT_MIME_TYPE);
+        assert_eq!(actual.payload(), "Hello".as_bytes());
+    }
+    #[test]
+    fn test_truncated_envelope() {
+        let bytes: Vec<u8> = testing_envelope().to_bytes().unwrap();
+        let envelope: Result<Envelope, Box<dyn Error>> = Envelope::try_from(&bytes[..bytes.len() - 1]);
+        assert!(envelope.is_err());
+    }
+    #[test]
+    fn test_oversized_fields() {
+        let envelope: Envelope = Envelope::new(vec![1], Some("a".repeat(70000)), String::from("text/plain"));
+        assert!(envelope.to_bytes().is_err());
+        let envelope: Envelope = Envelope::new(vec![1], None, "a".repeat(70000));
+        assert!(envelope.to_bytes().is_err());
+    }
+    #[test]
+    fn test_compressed_envelope() {
+        let message: String = "This is where your secret message will be! ".repeat(20);
+        let envelope: Envelope = Envelope::from_message(&message).with_compression(Compression::Deflate);
+        let bytes: Vec<u8> = envelope.to_bytes().unwrap();
+        assert!(bytes.len() < message.len());
+
+        let actual: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(actual.compression(), Compression::Deflate);
+        assert_eq!(actual.payload(), message.as_bytes());
+    }
+    #[test]
+    fn test_incompressible_envelope() {
+        let envelope: Envelope = testing_envelope().with_compression(Compression::Brotli);
+        let actual: Envelope = Envelope::try_from(envelope.to_bytes().unwrap().as_ref()).unwrap();
+        assert_eq!(actual.compression(), Compression::None);
+        assert_eq!(actual.payload(), envelope.payload());
+    }
+    #[test]
+    fn test_version_1_envelope() {
+        let bytes: Vec<u8> = Envelope::MAGIC
+            .iter()
+            .chain([1, 0, 0, 0, 0, 0, 0, 0, 2].iter())
+            .chain("Hi".as_bytes().iter())
+            .copied().collect();
+        let envelope: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(envelope.payload(), "Hi".as_bytes());
+        assert_eq!(envelope.compression(), Compression::None);
+    }
+    #[test]
+    fn test_version_2_envelope() {
+        let bytes: Vec<u8> = Envelope::MAGIC
+            .iter()
+            .chain([2, 0, 0, 0, 0, 0, 0, 0, 0, 2].iter())
+            .chain("Hi".as_bytes().iter())
+            .copied().collect();
+        let envelope: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(envelope.payload(), "Hi".as_bytes());
+    }
+    #[test]
+    fn test_version_3_envelope() {
+        let bytes: Vec<u8> = Envelope::MAGIC
+            .iter()
+            .chain([3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2].iter())
+            .chain("Hi".as_bytes().iter())
+            .copied().collect();
+        let envelope: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(envelope.payload(), "Hi".as_bytes());
+        assert!(!envelope.is_encrypted());
+    }
+    #[test]
+    fn test_error_corrected_envelope() {
+        let envelope: Envelope = testing_envelope().with_error_correction(8);
+        let mut bytes: Vec<u8> = envelope.to_bytes().unwrap();
+        bytes[5] = 0;
+        bytes[12] ^= 0xFF;
+        bytes[20] ^= 0xFF;
+
+        let actual: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(actual.payload(), envelope.payload());
+        assert_eq!(actual.filename(), Some("secret.pdf"));
+        assert_eq!(actual.parity(), 8);
+        assert_eq!(actual.corrected_bytes(), 3);
+    }
+    #[test]
+    fn test_version_4_envelope() {
+        let bytes: Vec<u8> = Envelope::MAGIC
+            .iter()
+            .chain([4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2].iter())
+            .chain("Hi".as_bytes().iter())
+            .copied().collect();
+        let envelope: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(envelope.payload(), "Hi".as_bytes());
+    }
+    #[test]
+    fn test_corrupted_header() {
+        let envelope: Envelope = testing_envelope().with_error_correction(8);
+        for index in [0, 3, 4, 8] {
+            let mut bytes: Vec<u8> = envelope.to_bytes().unwrap();
+            bytes[index] ^= 0x5A;
+            assert!(Envelope::is_envelope(&bytes));
+            let actual: Envelope = Envelope::try_from(bytes.as_ref()).unwrap();
+            assert_eq!(actual.payload(), envelope.payload());
+            assert_eq!(actual.corrected_bytes(), 1);
+        }
+        let mut bytes: Vec<u8> = envelope.to_bytes().unwrap();
+        bytes[0] ^= 0x5A;
+        bytes[1] ^= 0x5A;
+        assert!(!Envelope::is_envelope(&bytes));
+    }
+    #[test]
+    fn test_encrypted_envelope() {
+        let envelope: Envelope = testing_envelope().with_error_correction(4);
+        let bytes: Vec<u8> = envelope.seal(Some("passphrase")).unwrap();
+        assert!(!bytes.windows(10).any(|window: &[u8]| window == "secret.pdf".as_bytes()));
+
+        assert!(Envelope::try_from(bytes.as_ref()).is_err());
+        assert!(!Envelope::is_readable(&bytes));
+        assert!(Envelope::is_readable(&envelope.to_bytes().unwrap()));
+        assert!(Envelope::open(&bytes, Some("wrong")).is_err());
+        let actual: Envelope = Envelope::open(&bytes, Some("passphrase")).unwrap();
+        assert!(actual.is_encrypted());
+        assert_eq!(actual.filename(), Some("secret.pdf"));
+        assert_eq!(actual.payload(), envelope.payload());
+    }
+    #[test]
+    fn test_legacy_chunk_data() {
+        let envelope: Envelope = Envelope::from_chunk_data("Plain message".as_bytes(), None).unwrap();
+        assert_eq!(envelope.payload(), "Plain message".as_bytes());
+        assert_eq!(envelope.mime_type(), TEXT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_envelope_json() {
+        let json: serde_json::Value = serde_json::to_value(Envelope::from_message("Hi")).unwrap();
+        assert_eq!(json["text"], "Hi");
+        assert_eq!(json["payload"], "SGk=");
+        assert_eq!(json["compression"], "none");
+        assert!(serde_json::to_value(testing_envelope()).unwrap()["text"].is_null());
+    }
+
+    #[test]
+    fn test_guess_mime_type() {
+        assert_eq!(guess_mime_type(Path::new("archive.ZIP")), "application/zip");
+        assert_eq!(guess_mime_type(Path::new("no_extension")), DEFAULT_MIME_TYPE);
+    }
+}
@@ -0,0 +1,93 @@
+use std::error::Error;
+
+use reed_solomon::{Buffer, Decoder, Encoder};
+
+// Maximum size of a Reed-Solomon block, data and parity bytes included
+pub const BLOCK_SIZE: usize = 255;
+// Maximum number of parity bytes per block, leaving room for some data
+pub const MAX_PARITY: u8 = 128;
+
+// Protects the given bytes with Reed-Solomon codes. The bytes are split into blocks of
+// `BLOCK_SIZE - parity` bytes, each followed by `parity` bytes of error correction code,
+// allowing up to `parity / 2` corrupted bytes to be repaired in each block.
+pub fn encode(bytes: &[u8], parity: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_parity(parity)?;
+    let encoder: Encoder = Encoder::new(parity as usize);
+    Ok(bytes
+        .chunks(BLOCK_SIZE - parity as usize)
+        .flat_map(|block: &[u8]| encoder.encode(block).to_vec())
+        .collect())
+}
+
+// Repairs and strips bytes produced by `encode` with the same parity.
+// Returns the original bytes along with the number of bytes that have been corrected.
+pub fn decode(bytes: &[u8], parity: u8) -> Result<(Vec<u8>, usize), Box<dyn Error>> {
+    check_parity(parity)?;
+    let decoder: Decoder = Decoder::new(parity as usize);
+    let mut result: Vec<u8> = vec![];
+    let mut corrected: usize = 0;
+    for (index, block) in bytes.chunks(BLOCK_SIZE).enumerate() {
+        if block.len() <= parity as usize {
+            return Err(format!(
+                "Invalid error correction block {} (only {} bytes for {} parity bytes)",
+                index, block.len(), parity
+            ).into());
+        }
+        let (repaired, errors): (Buffer, usize) = decoder.correct_err_count(block, None)
+            .map_err(|_| format!("Too many corrupted bytes in error correction block {}", index))?;
+        result.extend_from_slice(repaired.data());
+        corrected += errors;
+    }
+    Ok((result, corrected))
+}
+
+fn check_parity(parity: u8) -> Result<(), Box<dyn Error>> {
+    if parity == 0 || parity > MAX_PARITY {
+        return Err(format!("Invalid parity length ({}), must be between 1 and {}", parity, MAX_PARITY).into());
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::fec;
+
+    fn testing_bytes() -> Vec<u8> {
+        (0..600).map(|i: u32| (i * 7 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let encoded: Vec<u8> = fec::encode(&testing_bytes(), 16).unwrap();
+        assert_eq!(encoded.len(), 600 + 3 * 16);
+
+        let (decoded, corrected) = fec::decode(&encoded, 16).unwrap();
+        assert_eq!(decoded, testing_bytes());
+        assert_eq!(corrected, 0);
+    }
+    #[test]
+    fn test_repair() {
+        let mut encoded: Vec<u8> = fec::encode(&testing_bytes(), 16).unwrap();
+        for index in [0, 10, 100, 300, 620] {
+            encoded[index] ^= 0xFF;
+        }
+
+        let (decoded, corrected) = fec::decode(&encoded, 16).unwrap();
+        assert_eq!(decoded, testing_bytes());
+        assert_eq!(corrected, 5);
+    }
+    #[test]
+    fn test_too_much_damage() {
+        let mut encoded: Vec<u8> = fec::encode(&testing_bytes(), 4).unwrap();
+        for byte in encoded.iter_mut().take(10) {
+            *byte ^= 0xFF;
+        }
+        assert!(fec::decode(&encoded, 4).is_err());
+    }
+    #[test]
+    fn test_invalid_parity() {
+        assert!(fec::encode(&testing_bytes(), 0).is_err());
+        assert!(fec::encode(&testing_bytes(), 200).is_err());
+    }
+}
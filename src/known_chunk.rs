@@ -0,0 +1,392 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::{Serialize, Serializer};
+
+use crate::chunk::Chunk;
+
+// Largest ICC profile inflated from an iCCP chunk, real profiles being well under a megabyte
+pub const MAX_ICC_PROFILE_LENGTH: usize = 4 * 1024 * 1024;
+
+/// Background colour of a bKGD chunk, whose layout depends on the colour type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Background {
+    PaletteIndex(u8),
+    Gray(u16),
+    Rgb([u16; 3]),
+}
+
+/// Transparency of a tRNS chunk, whose layout depends on the colour type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transparency {
+    // Alpha value of the first palette entries
+    PaletteAlphas(Vec<u8>),
+    Gray(u16),
+    Rgb([u16; 3]),
+}
+
+/// An entry of a suggested palette, samples being 8 or 16-bit as given by the sPLT chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+/// The decoded content of a standard ancillary chunk.
+/// See http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Ancillary-chunks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KnownChunk {
+    // gAMA: image gamma times 100000
+    Gamma { gamma: u32 },
+    // cHRM: x and y of the white point and primaries, times 100000
+    Chromaticities { white: [u32; 2], red: [u32; 2], green: [u32; 2], blue: [u32; 2] },
+    // sRGB
+    StandardRgb { rendering_intent: u8 },
+    // iCCP, the profile being inflated
+    IccProfile { name: String, #[serde(rename = "profile_length", serialize_with = "byte_length")] profile: Vec<u8> },
+    // pHYs: pixels per unit, the unit being the meter (1) or unknown (0)
+    PhysicalDimensions { x: u32, y: u32, unit: u8 },
+    // sBIT: significant bits of each channel
+    SignificantBits { bits: Vec<u8> },
+    // bKGD
+    Background { color: Background },
+    // hIST: approximate usage frequency of each palette entry
+    Histogram { frequencies: Vec<u16> },
+    // tRNS
+    Transparency { transparency: Transparency },
+    // sPLT
+    SuggestedPalette { name: String, sample_depth: u8, entries: Vec<PaletteEntry> },
+    // tIME, in UTC
+    Time { year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8 },
+    // eXIf: an Exif profile, starting with its byte order mark
+    Exif { #[serde(rename = "data_length", serialize_with = "byte_length")] data: Vec<u8> },
+}
+impl KnownChunk {
+    // The type of the chunk holding this content
+    pub fn chunk_type(&self) -> [u8; 4] {
+        match self {
+            KnownChunk::Gamma { .. } => *b"gAMA",
+            KnownChunk::Chromaticities { .. } => *b"cHRM",
+            KnownChunk::StandardRgb { .. } => *b"sRGB",
+            KnownChunk::IccProfile { .. } => *b"iCCP",
+            KnownChunk::PhysicalDimensions { .. } => *b"pHYs",
+            KnownChunk::SignificantBits { .. } => *b"sBIT",
+            KnownChunk::Background { .. } => *b"bKGD",
+            KnownChunk::Histogram { .. } => *b"hIST",
+            KnownChunk::Transparency { .. } => *b"tRNS",
+            KnownChunk::SuggestedPalette { .. } => *b"sPLT",
+            KnownChunk::Time { .. } => *b"tIME",
+            KnownChunk::Exif { .. } => *b"eXIf",
+        }
+    }
+
+    // Serializes this content into a chunk, for instance to build a plausible cover chunk
+    pub fn to_chunk(&self) -> Result<Chunk, Box<dyn Error>> {
+        let data: Vec<u8> = match self {
+            KnownChunk::Gamma { gamma } => gamma.to_be_bytes().to_vec(),
+            KnownChunk::Chromaticities { white, red, green, blue } => [white, red, green, blue]
+                .iter()
+                .flat_map(|point: &&[u32; 2]| point.iter().flat_map(|value: &u32| value.to_be_bytes()))
+                .collect(),
+            KnownChunk::StandardRgb { rendering_intent } => vec![*rendering_intent],
+            KnownChunk::IccProfile { name, profile } => {
+                let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::best());
+                encoder.write_all(profile)?;
+                // Null separator, then compression method 0 (deflate)
+                [keyword(name)?, vec![0, 0], encoder.finish()?].concat()
+            }
+            KnownChunk::PhysicalDimensions { x, y, unit } => [&x.to_be_bytes()[..], &y.to_be_bytes(), &[*unit]].concat(),
+            KnownChunk::SignificantBits { bits } => bits.clone(),
+            KnownChunk::Background { color } => match color {
+                Background::PaletteIndex(index) => vec![*index],
+                Background::Gray(gray) => gray.to_be_bytes().to_vec(),
+                Background::Rgb(rgb) => rgb.iter().flat_map(|sample: &u16| sample.to_be_bytes()).collect(),
+            },
+            KnownChunk::Histogram { frequencies } => frequencies.iter().flat_map(|frequency: &u16| frequency.to_be_bytes()).collect(),
+            KnownChunk::Transparency { transparency } => match transparency {
+                Transparency::PaletteAlphas(alphas) => alphas.clone(),
+                Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+                Transparency::Rgb(rgb) => rgb.iter().flat_map(|sample: &u16| sample.to_be_bytes()).collect(),
+            },
+            KnownChunk::SuggestedPalette { name, sample_depth, entries } => {
+                let mut data: Vec<u8> = [keyword(name)?, vec![0, *sample_depth]].concat();
+                for entry in entries {
+                    for sample in [entry.red, entry.green, entry.blue, entry.alpha] {
+                        match sample_depth {
+                            8 => data.push(sample as u8),
+                            _ => data.extend(sample.to_be_bytes()),
+                        }
+                    }
+                    data.extend(entry.frequency.to_be_bytes());
+                }
+                data
+            }
+            KnownChunk::Time { year, month, day, hour, minute, second } => {
+                [&year.to_be_bytes()[..], &[*month, *day, *hour, *minute, *second]].concat()
+            }
+            KnownChunk::Exif { data } => data.clone(),
+        };
+        Ok(Chunk::new(self.chunk_type(), data))
+    }
+}
+impl TryFrom<&Chunk> for KnownChunk {
+    type Error = Box<dyn Error>;
+
+    // bKGD and tRNS chunks are told apart by their length, the colour type being unknown here
+    fn try_from(chunk: &Chunk) -> Result<Self, Box<dyn Error>> {
+        let data: &[u8] = chunk.data();
+        let chunk_type: String = chunk.chunk_type().to_string();
+        let expect_length = |lengths: &[usize]| -> Result<(), Box<dyn Error>> {
+            if lengths.contains(&data.len()) {
+                return Ok(());
+            }
+            let expected: Vec<String> = lengths.iter().map(usize::to_string).collect();
+            Err(format!("Invalid {} chunk ({} bytes expected, {} found)", chunk_type, expected.join(" or "), data.len()).into())
+        };
+        let u16_at = |offset: usize| -> u16 { u16::from_be_bytes([data[offset], data[offset + 1]]) };
+        let u32_at = |offset: usize| -> u32 { u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) };
+
+        Ok(match &chunk.chunk_type().bytes() {
+            b"gAMA" => {
+                expect_length(&[4])?;
+                KnownChunk::Gamma{gamma: u32_at(0)}
+            }
+            b"cHRM" => {
+                expect_length(&[32])?;
+                let point = |index: usize| -> [u32; 2] { [u32_at(index * 8), u32_at(index * 8 + 4)] };
+                KnownChunk::Chromaticities{white: point(0), red: point(1), green: point(2), blue: point(3)}
+            }
+            b"sRGB" => {
+                expect_length(&[1])?;
+                KnownChunk::StandardRgb{rendering_intent: data[0]}
+            }
+            b"iCCP" => {
+                let (name, rest) = split_keyword(data, &chunk_type)?;
+                if rest.first() != Some(&0) {
+                    return Err("Invalid iCCP chunk (unknown compression method)".into());
+                }
+                let mut profile: Vec<u8> = vec![];
+                ZlibDecoder::new(&rest[1..]).take(MAX_ICC_PROFILE_LENGTH as u64 + 1).read_to_end(&mut profile)?;
+                if profile.len() > MAX_ICC_PROFILE_LENGTH {
+                    return Err(format!("Invalid iCCP chunk (profile larger than {} bytes)", MAX_ICC_PROFILE_LENGTH).into());
+                }
+                KnownChunk::IccProfile{name, profile}
+            }
+            b"pHYs" => {
+                expect_length(&[9])?;
+                KnownChunk::PhysicalDimensions{x: u32_at(0), y: u32_at(4), unit: data[8]}
+            }
+            b"sBIT" => {
+                expect_length(&[1, 2, 3, 4])?;
+                KnownChunk::SignificantBits{bits: data.to_vec()}
+            }
+            b"bKGD" => {
+                expect_length(&[1, 2, 6])?;
+                let color: Background = match data.len() {
+                    1 => Background::PaletteIndex(data[0]),
+                    2 => Background::Gray(u16_at(0)),
+                    _ => Background::Rgb([u16_at(0), u16_at(2), u16_at(4)]),
+                };
+                KnownChunk::Background{color}
+            }
+            b"hIST" => {
+                if !data.len().is_multiple_of(2) {
+                    return Err("Invalid hIST chunk (odd length)".into());
+                }
+                KnownChunk::Histogram{frequencies: (0..data.len()).step_by(2).map(u16_at).collect()}
+            }
+            b"tRNS" => {
+                let transparency: Transparency = match data.len() {
+                    2 => Transparency::Gray(u16_at(0)),
+                    6 => Transparency::Rgb([u16_at(0), u16_at(2), u16_at(4)]),
+                    _ => Transparency::PaletteAlphas(data.to_vec()),
+                };
+                KnownChunk::Transparency{transparency}
+            }
+            b"sPLT" => {
+                let (name, rest) = split_keyword(data, &chunk_type)?;
+                let sample_depth: u8 = *rest.first().ok_or("Invalid sPLT chunk (missing sample depth)")?;
+                let entry_length: usize = match sample_depth {
+                    8 => 6,
+                    16 => 10,
+                    _ => return Err(format!("Invalid sPLT chunk (sample depth {})", sample_depth).into()),
+                };
+                if !(rest.len() - 1).is_multiple_of(entry_length) {
+                    return Err("Invalid sPLT chunk (truncated entry)".into());
+                }
+                let entries: Vec<PaletteEntry> = rest[1..]
+                    .chunks(entry_length)
+                    .map(|entry: &[u8]| {
+                        let sample = |index: usize| -> u16 {
+                            match sample_depth {
+                                8 => entry[index] as u16,
+                                _ => u16::from_be_bytes([entry[index * 2], entry[index * 2 + 1]]),
+                            }
+                        };
+                        PaletteEntry{
+                            red: sample(0),
+                            green: sample(1),
+                            blue: sample(2),
+                            alpha: sample(3),
+                            frequency: u16::from_be_bytes([entry[entry_length - 2], entry[entry_length - 1]]),
+                        }
+                    })
+                    .collect();
+                KnownChunk::SuggestedPalette{name, sample_depth, entries}
+            }
+            b"tIME" => {
+                expect_length(&[7])?;
+                KnownChunk::Time{year: u16_at(0), month: data[2], day: data[3], hour: data[4], minute: data[5], second: data[6]}
+            }
+            b"eXIf" => KnownChunk::Exif{data: data.to_vec()},
+            _ => return Err(format!("{} chunks have no typed support", chunk_type).into()),
+        })
+    }
+}
+impl fmt::Display for KnownChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fixed = |value: u32| -> f64 { value as f64 / 100000.0 };
+        match self {
+            KnownChunk::Gamma { gamma } => write!(f, "gamma {:.5}", fixed(*gamma)),
+            KnownChunk::Chromaticities { white, red, green, blue } => write!(
+                f, "white {:.4},{:.4} red {:.4},{:.4} green {:.4},{:.4} blue {:.4},{:.4}",
+                fixed(white[0]), fixed(white[1]), fixed(red[0]), fixed(red[1]),
+                fixed(green[0]), fixed(green[1]), fixed(blue[0]), fixed(blue[1])
+            ),
+            KnownChunk::StandardRgb { rendering_intent } => {
+                let intent: &str = match rendering_intent {
+                    0 => "perceptual",
+                    1 => "relative colorimetric",
+                    2 => "saturation",
+                    3 => "absolute colorimetric",
+                    _ => "unknown",
+                };
+                write!(f, "rendering intent {} ({})", rendering_intent, intent)
+            }
+            KnownChunk::IccProfile { name, profile } => write!(f, "{}: {} bytes ICC profile", name, profile.len()),
+            KnownChunk::PhysicalDimensions { x, y, unit } => {
+                write!(f, "{}x{} pixels per {}", x, y, if *unit == 1 { "meter" } else { "unit" })
+            }
+            KnownChunk::SignificantBits { bits } => write!(
+                f, "significant bits {}", bits.iter().map(u8::to_string).collect::<Vec<String>>().join(",")
+            ),
+            KnownChunk::Background { color } => match color {
+                Background::PaletteIndex(index) => write!(f, "background palette index {}", index),
+                Background::Gray(gray) => write!(f, "background gray {}", gray),
+                Background::Rgb([red, green, blue]) => write!(f, "background rgb({}, {}, {})", red, green, blue),
+            },
+            KnownChunk::Histogram { frequencies } => write!(f, "{} palette entry frequencies", frequencies.len()),
+            KnownChunk::Transparency { transparency } => match transparency {
+                Transparency::PaletteAlphas(alphas) => write!(f, "{} palette alpha values", alphas.len()),
+                Transparency::Gray(gray) => write!(f, "transparent gray {}", gray),
+                Transparency::Rgb([red, green, blue]) => write!(f, "transparent rgb({}, {}, {})", red, green, blue),
+            },
+            KnownChunk::SuggestedPalette { name, sample_depth, entries } => {
+                write!(f, "{}: {} entries, {}-bit", name, entries.len(), sample_depth)
+            }
+            KnownChunk::Time { year, month, day, hour, minute, second } => {
+                write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second)
+            }
+            KnownChunk::Exif { data } => {
+                let byte_order: &str = match data.get(0..2) {
+                    Some(b"MM") => ", big-endian",
+                    Some(b"II") => ", little-endian",
+                    _ => "",
+                };
+                write!(f, "{} bytes of Exif data{}", data.len(), byte_order)
+            }
+        }
+    }
+}
+
+// Splits the null-terminated Latin-1 keyword starting the data of iCCP and sPLT chunks
+fn split_keyword<'a>(data: &'a [u8], chunk_type: &str) -> Result<(String, &'a [u8]), Box<dyn Error>> {
+    let end: usize = data.iter()
+        .position(|byte: &u8| *byte == 0)
+        .ok_or(format!("Invalid {} chunk (unterminated keyword)", chunk_type))?;
+    Ok((data[..end].iter().map(|byte: &u8| *byte as char).collect(), &data[end + 1..]))
+}
+
+// Encodes a keyword in Latin-1, which the spec limits to 1 to 79 characters
+fn keyword(name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let bytes: Vec<u8> = name.chars()
+        .map(|c: char| u8::try_from(c as u32).map_err(|_| format!("Keyword {} is not Latin-1", name)))
+        .collect::<Result<Vec<u8>, String>>()?;
+    if bytes.is_empty() || bytes.len() > 79 {
+        return Err(format!("Keyword {} must be 1 to 79 characters long", name).into());
+    }
+    Ok(bytes)
+}
+
+// Serializes bytes as their length, the bytes themselves being left to extract-chunk
+fn byte_length<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(bytes.len() as u64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::known_chunk::{Background, KnownChunk, PaletteEntry, Transparency, MAX_ICC_PROFILE_LENGTH};
+
+    fn round_trip(known: KnownChunk) {
+        let chunk: Chunk = known.to_chunk().unwrap();
+        assert_eq!(KnownChunk::try_from(&chunk).unwrap(), known);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        round_trip(KnownChunk::Gamma{gamma: 45455});
+        round_trip(KnownChunk::Chromaticities{white: [31270, 32900], red: [64000, 33000], green: [30000, 60000], blue: [15000, 6000]});
+        round_trip(KnownChunk::StandardRgb{rendering_intent: 0});
+        round_trip(KnownChunk::IccProfile{name: String::from("Display P3"), profile: vec![7; 300]});
+        round_trip(KnownChunk::PhysicalDimensions{x: 2835, y: 2835, unit: 1});
+        round_trip(KnownChunk::SignificantBits{bits: vec![5, 6, 5]});
+        round_trip(KnownChunk::Background{color: Background::PaletteIndex(3)});
+        round_trip(KnownChunk::Background{color: Background::Rgb([255, 128, 0])});
+        round_trip(KnownChunk::Histogram{frequencies: vec![1, 200, 65535]});
+        round_trip(KnownChunk::Transparency{transparency: Transparency::PaletteAlphas(vec![0, 128, 255])});
+        round_trip(KnownChunk::Transparency{transparency: Transparency::Gray(0)});
+        round_trip(KnownChunk::SuggestedPalette{name: String::from("web"), sample_depth: 16, entries: vec![
+            PaletteEntry{red: 65535, green: 0, blue: 512, alpha: 65535, frequency: 10},
+        ]});
+        round_trip(KnownChunk::Time{year: 2024, month: 2, day: 29, hour: 23, minute: 59, second: 60});
+        round_trip(KnownChunk::Exif{data: b"MM\x00\x2a\x00\x00\x00\x08".to_vec()});
+    }
+    #[test]
+    fn test_invalid_chunks() {
+        assert!(KnownChunk::try_from(&Chunk::new(*b"gAMA", vec![0; 3])).is_err());
+        assert!(KnownChunk::try_from(&Chunk::new(*b"iCCP", b"name".to_vec())).is_err());
+        assert!(KnownChunk::try_from(&Chunk::new(*b"sPLT", b"name\x00\x08\x01".to_vec())).is_err());
+        assert!(KnownChunk::try_from(&Chunk::new(*b"ruSt", vec![])).is_err());
+        assert!(KnownChunk::IccProfile{name: String::new(), profile: vec![]}.to_chunk().is_err());
+
+        let oversized: KnownChunk = KnownChunk::IccProfile{name: String::from("bomb"), profile: vec![0; MAX_ICC_PROFILE_LENGTH + 1]};
+        assert!(KnownChunk::try_from(&oversized.to_chunk().unwrap()).is_err());
+    }
+    #[test]
+    fn test_known_chunk_json() {
+        let icc: KnownChunk = KnownChunk::IccProfile{name: String::from("Display P3"), profile: vec![7; 300]};
+        let json: serde_json::Value = serde_json::to_value(icc).unwrap();
+        assert_eq!(json["profile_length"], 300);
+        assert!(json.get("profile").is_none());
+        let json: serde_json::Value = serde_json::to_value(KnownChunk::Exif{data: b"II*\x00".to_vec()}).unwrap();
+        assert_eq!(json["data_length"], 4);
+    }
+    #[test]
+    fn test_known_chunk_display() {
+        let chunk: Chunk = Chunk::new(*b"sRGB", vec![1]);
+        assert_eq!(KnownChunk::try_from(&chunk).unwrap().to_string(), "rendering intent 1 (relative colorimetric)");
+        let exif: KnownChunk = KnownChunk::Exif{data: b"II*\x00".to_vec()};
+        assert_eq!(exif.to_string(), "4 bytes of Exif data, little-endian");
+    }
+}
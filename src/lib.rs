@@ -0,0 +1,54 @@
+//! Hide messages and files inside PNG images, as custom chunks.
+//!
+//! The building blocks are [`Png`], [`Chunk`] and [`ChunkType`], following the
+//! [PNG spec](http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html). Payloads are wrapped
+//! in an [`Envelope`] keeping their metadata, optional compression, encryption and error correction,
+//! then hidden by a [`Carrier`].
+//! [`Encoder`] and [`Decoder`] tie everything together.
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod analysis;
+pub mod batch;
+pub mod carrier;
+pub mod chunk;
+pub mod chunk_type;
+pub mod compression;
+pub mod crypto;
+pub mod diff;
+pub mod envelope;
+pub mod fec;
+pub mod known_chunk;
+pub mod pixels;
+pub mod png;
+pub mod registry;
+pub mod repair;
+pub mod report;
+pub mod scan;
+pub mod stego;
+pub mod strip;
+
+pub use crate::carrier::{Carrier, CarrierRegistry, ChunkCarrier, Placement};
+pub use crate::chunk::Chunk;
+pub use crate::chunk_type::ChunkType;
+pub use crate::compression::Compression;
+pub use crate::envelope::Envelope;
+pub use crate::known_chunk::KnownChunk;
+pub use crate::png::{ParseMode, Png};
+pub use crate::stego::{Decoder, Encoder};
+
+// Hides an envelope in a new chunk of the given type, inserted somewhere in the PNG
+pub fn encode_message(png: &mut Png, chunk_type: &str, envelope: &Envelope) -> Result<(), Box<dyn Error>> {
+    let chunk_type: ChunkType = ChunkType::from_str(chunk_type)
+        .map_err(|_| format!("Invalid chunk type: {}", chunk_type))?;
+    *png = Encoder::new(png.clone()).method(Arc::new(ChunkCarrier::new(chunk_type))).embed(envelope.clone())?;
+    Ok(())
+}
+
+// Reads back the envelope hidden in the first chunk of the given type, if any
+pub fn decode_message(png: &Png, chunk_type: &str) -> Result<Option<Envelope>, Box<dyn Error>> {
+    let chunk_type: ChunkType = ChunkType::from_str(chunk_type)
+        .map_err(|_| format!("Invalid chunk type: {}", chunk_type))?;
+    Decoder::new(png).method(Arc::new(ChunkCarrier::new(chunk_type))).extract()
+}
@@ -1,60 +1,355 @@
+use std::env;
 use std::error::Error;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use clap::Parser;
-use crate::chunk::Chunk;
-use crate::chunk_type::ChunkType;
-use crate::commands::Commands;
-use crate::png::Png;
+use rand::Rng;
+use img_msg_encoder::analysis::ChannelAnalysis;
+use img_msg_encoder::batch::{self, FileResult};
+use img_msg_encoder::diff::PixelDiff;
+use img_msg_encoder::report::{self, is_stdio, OutputFormat};
+use img_msg_encoder::scan::Finding;
+use img_msg_encoder::strip::Stripped;
+use img_msg_encoder::{
+    analysis, carrier, diff, repair, scan, strip, Carrier, Chunk, ChunkType, Decoder, Encoder, Envelope, ParseMode, Placement, Png
+};
+use crate::commands::{ChunkSelector, Commands, OutputArgs, PassphraseArgs};
 
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
+mod output;
+
+// Environment variable holding the passphrase when neither --passphrase-file nor
+// --ask-passphrase is given
+const PASSPHRASE_VARIABLE: &str = "IMG_MSG_ENCODER_PASSPHRASE";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli: commands::Cli = commands::Cli::parse();
+    let mode: ParseMode = cli.parse_mode();
+    let format: OutputFormat = cli.format;
+    let input: &Path = cli.command.filename();
+    // Read once, so that a batch prompts a single time
+    let passphrase: Option<String> = match cli.command.passphrase() {
+        Some(passphrase_args) => passphrase(passphrase_args)?,
+        None => None,
+    };
+
+    if !batch::is_batch(input) {
+        return run(&cli.command, input, mode, format, passphrase.as_deref(), &mut io::stdout());
+    }
+    check_batch(&cli.command)?;
+    let files: Vec<PathBuf> = batch::expand(input, cli.recursive)?;
+    let results: Vec<FileResult> = batch::run(&files, |path: &Path, out: &mut Vec<u8>| {
+        run(&cli.command, path, mode, format, passphrase.as_deref(), out)
+    });
+    report::batch(format, &results)
+}
 
-    match &cli.command {
+// Runs a command on a single PNG file, writing what it prints to `out`
+fn run(
+    command: &Commands, input: &Path, mode: ParseMode, format: OutputFormat, passphrase: Option<&str>,
+    out: &mut dyn Write
+) -> Result<(), Box<dyn Error>> {
+    match command {
         Commands::Encode(encode_args) => {
-            let mut png: Png = Png::from_file(encode_args.filename.as_os_str())?;
-            let chunk: Chunk = Chunk::new(
-                <&[u8] as TryInto<[u8; 4]>>::try_into(encode_args.chunk_type.as_bytes()).unwrap(),
-                encode_args.message.as_bytes().to_vec()
-            );
-            png.append_chunk_somewhere(chunk);
-            fs::write(encode_args.output_filename.to_owned().unwrap(), png.as_bytes())?;
+            if is_stdio(input) && encode_args.message_file.as_deref().is_some_and(is_stdio) {
+                return Err("The PNG and the message file cannot both be read from stdin".into());
+            }
+            let original: Png = read_png(input, mode)?;
+            let envelope: Envelope = match (&encode_args.message, &encode_args.message_file) {
+                (_, Some(message_file)) => Envelope::from_file(message_file)?,
+                (Some(message), None) => Envelope::from_message(message),
+                (None, None) => return Err("Either a message or a message file is required".into()),
+            };
+            let chunk_type_name: Option<String> = match &encode_args.chunk_type {
+                Some(chunk_type_name) => Some(chunk_type_name.clone()),
+                None if encode_args.auto_type => Some(ChunkType::random_private().to_string()),
+                None => None,
+            };
+            let method: Arc<dyn Carrier> = select_carrier(&encode_args.method, chunk_type_name.as_deref())?;
+            // Only the chunk method hides the payload in a chunk of that type
+            let chunk_type_name: Option<&str> = chunk_type_name.as_deref().filter(|_| encode_args.method == "chunk");
+            warn_unsafe_to_copy(method.as_ref(), &original);
+            let mut encoder: Encoder = Encoder::new(original.clone())
+                .method(method)
+                .placement(encode_args.placement)
+                .compress(encode_args.compress)
+                .error_correction(encode_args.ecc);
+            if let Some(passphrase) = passphrase {
+                encoder = encoder.passphrase(passphrase);
+            }
+            let png: Png = encoder.embed(envelope)?;
+            let output_filename: PathBuf = output::destination(&encode_args.output, input, "encoded");
+            if encode_args.output.dry_run {
+                return dry_run(out, format, &encode_args.output, &output_filename, &original, &png);
+            }
+            output::write_png(&encode_args.output, &output_filename, &png.as_bytes())?;
+            report::encoded(
+                &mut report_stream(out, is_stdio(&output_filename)), format,
+                &output_filename, &encode_args.method, chunk_type_name, encode_args.auto_type
+            )?;
         }
         Commands::Decode(decode_args) => {
-            let png: Png = Png::from_file(decode_args.filename.as_os_str())?;
-            let chunk: Option<&Chunk> = png.chunk_by_type(decode_args.chunk_type.as_str());
-            match chunk {
-                Some(chunk) => {
-                    println!("The following chunk has been found for you: {}", chunk);
-                }
-                None => {
-                    println!("No message for you here!");
+            let png: Png = read_png(input, mode)?;
+            let method: Arc<dyn Carrier> = select_carrier(&decode_args.method, decode_args.chunk_type.as_deref())?;
+            if let (Some(chunk_type), "chunk") = (&decode_args.chunk_type, method.name()) {
+                if png.chunk_by_type(chunk_type).is_some_and(|chunk: &Chunk| chunk.is_damaged()) {
+                    eprintln!("Warning: the {} chunk is damaged, its content may be altered", chunk_type);
                 }
             }
+            let mut decoder: Decoder = Decoder::new(&png).method(method);
+            if let Some(passphrase) = passphrase {
+                decoder = decoder.passphrase(passphrase);
+            }
+            let envelope: Option<Envelope> = decoder.extract()?;
+            if let (Some(envelope), Some(output_filename)) = (&envelope, &decode_args.output_filename) {
+                output::write(output_filename, envelope.payload(), decode_args.force, false)?;
+            }
+            report::decoded(
+                &mut report_stream(out, decode_args.output_filename.as_deref().is_some_and(is_stdio)), format,
+                envelope.as_ref(), decode_args.output_filename.as_deref()
+            )?;
         }
         Commands::Remove(remove_args) => {
-            let mut png: Png = Png::from_file(remove_args.filename.as_os_str())?;
-            let result_removal: Result<Chunk, Box<dyn Error>> = png.remove_first_chunk(
-                remove_args.chunk_type.as_str()
-            );
-            match result_removal {
-                Ok(chunk) => {
-                    println!("Removing: {}", chunk);
-                    fs::write(remove_args.filename.to_owned(), png.as_bytes())?;
-                }
-                Err(err) => {println!("{}", err);}
+            let original: Png = read_png(input, mode)?;
+            let method: Arc<dyn Carrier> = select_carrier(&remove_args.method, remove_args.chunk_type.as_deref())?;
+            let mut png: Png = original.clone();
+            let removed: usize = method.remove(&mut png)?;
+            if removed != 0 {
+                warn_unsafe_to_copy(method.as_ref(), &original);
+            }
+            let output_filename: PathBuf = output::destination(&remove_args.output, input, "removed");
+            if remove_args.output.dry_run {
+                return dry_run(out, format, &remove_args.output, &output_filename, &original, &png);
+            }
+            // A pipeline expects the PNG on stdout even when it is left unchanged
+            if removed != 0 || is_stdio(&output_filename) {
+                output::write_png(&remove_args.output, &output_filename, &png.as_bytes())?;
+            }
+            report::removed(&mut report_stream(out, is_stdio(&output_filename)), format, method.name(), removed)?;
+        }
+        Commands::Print(_) => report::png(out, format, &read_png(input, mode)?)?,
+        Commands::List(_) => report::chunk_table(out, format, &read_png(input, mode)?)?,
+        Commands::Dump(dump_args) => {
+            let png: Png = read_png(input, mode)?;
+            let (index, chunk) = select_chunk(&png, &dump_args.chunk)?;
+            report::dump(out, format, index, &chunk)?;
+        }
+        Commands::ExtractChunk(extract_args) => {
+            let png: Png = read_png(input, mode)?;
+            let (index, chunk) = select_chunk(&png, &extract_args.chunk)?;
+            let bytes: Vec<u8> = if extract_args.full { chunk.as_bytes() } else { chunk.data().to_vec() };
+            output::write(&extract_args.output_filename, &bytes, extract_args.force, false)?;
+            report::extracted(
+                &mut report_stream(out, is_stdio(&extract_args.output_filename)), format,
+                index, &chunk, &extract_args.output_filename, bytes.len()
+            )?;
+        }
+        Commands::InsertChunk(insert_args) => {
+            if is_stdio(input) && is_stdio(&insert_args.chunk_filename) {
+                return Err("The PNG and the chunk file cannot both be read from stdin".into());
+            }
+            let mut png: Png = read_png(input, mode)?;
+            let bytes: Vec<u8> = if is_stdio(&insert_args.chunk_filename) {
+                let mut bytes: Vec<u8> = vec![];
+                io::stdin().read_to_end(&mut bytes)?;
+                bytes
+            } else {
+                fs::read(&insert_args.chunk_filename)?
+            };
+            let chunk: Chunk = Chunk::try_from(bytes.as_slice())?;
+            if chunk.as_bytes().len() != bytes.len() {
+                return Err(format!(
+                    "{} holds {} bytes after the chunk, a single serialized chunk is expected",
+                    insert_args.chunk_filename.display(), bytes.len() - chunk.as_bytes().len()
+                ).into());
+            }
+            let index: usize = match insert_args.index {
+                Some(index) if png.insertion_range().contains(&index) => index,
+                Some(index) => return Err(format!(
+                    "Chunk index {} is out of the insertion range ({}..={})",
+                    index, png.insertion_range().start(), png.insertion_range().end()
+                ).into()),
+                None => match insert_args.placement {
+                    Placement::Random => rand::thread_rng().gen_range(png.insertion_range()),
+                    Placement::AfterHeader => *png.insertion_range().start(),
+                    Placement::BeforeEnd => *png.insertion_range().end(),
+                },
+            };
+            let original: Png = png.clone();
+            png.insert_chunk(index, chunk.clone());
+            let output_filename: PathBuf = output::destination(&insert_args.output, input, "inserted");
+            if insert_args.output.dry_run {
+                return dry_run(out, format, &insert_args.output, &output_filename, &original, &png);
+            }
+            output::write_png(&insert_args.output, &output_filename, &png.as_bytes())?;
+            report::inserted(&mut report_stream(out, is_stdio(&output_filename)), format, index, &chunk, &output_filename)?;
+        }
+        Commands::Scan(scan_args) => {
+            let findings: Vec<Finding> = scan::scan(&read_png(input, mode)?, scan_args.max_text, scan_args.pixels);
+            report::findings(out, format, &findings)?;
+        }
+        Commands::Analyze(_) => {
+            let analyses: Vec<ChannelAnalysis> = analysis::analyze(&read_png(input, mode)?)?;
+            report::analysis(out, format, &analyses)?;
+        }
+        Commands::Strip(strip_args) => {
+            for kept in strip_args.keep.iter() {
+                chunk_type(kept)?;
+            }
+            let original: Png = read_png(input, mode)?;
+            let mut png: Png = original.clone();
+            let stripped: Stripped = strip::strip(&mut png, &strip_args.keep, strip_args.metadata);
+            let output_filename: PathBuf = output::destination(&strip_args.output, input, "stripped");
+            if strip_args.output.dry_run {
+                return dry_run(out, format, &strip_args.output, &output_filename, &original, &png);
+            }
+            if !stripped.is_empty() || is_stdio(&output_filename) {
+                output::write_png(&strip_args.output, &output_filename, &png.as_bytes())?;
             }
+            report::stripped(&mut report_stream(out, is_stdio(&output_filename)), format, &stripped, &output_filename)?;
         }
-        Commands::Print(print_args) => {
-            let png: Png = Png::from_file(print_args.filename.as_os_str())?;
-            println!("{}", png);
+        Commands::Diff(diff_args) => {
+            if is_stdio(input) && is_stdio(&diff_args.other_filename) {
+                return Err("The two PNGs cannot both be read from stdin".into());
+            }
+            let before: Png = read_png(input, mode)?;
+            let after: Png = read_png(&diff_args.other_filename, mode)?;
+            let pixels: Option<PixelDiff> = if diff_args.pixels { Some(diff::pixel_diff(&before, &after)?) } else { None };
+            report::diff(out, format, &before, &after, pixels.as_ref())?;
+        }
+        Commands::Repair(repair_args) => {
+            let png: Png = read_png(input, ParseMode::Lenient)?;
+            let (repaired, fixes) = repair::repair(&png)?;
+            let problems: Vec<String> = repair::check(&repaired);
+            let output_filename: PathBuf = output::destination(&repair_args.output, input, "repaired");
+            if repair_args.output.dry_run {
+                return dry_run(out, format, &repair_args.output, &output_filename, &png, &repaired);
+            }
+            if !fixes.is_empty() || is_stdio(&output_filename) {
+                output::write_png(&repair_args.output, &output_filename, &repaired.as_bytes())?;
+            }
+            report::repaired(&mut report_stream(out, is_stdio(&output_filename)), format, &fixes, &problems)?;
         }
     }
 
     Ok(())
 }
+
+// Fails if a command cannot run on several files, because of a single output file or
+// another input read from stdin
+fn check_batch(command: &Commands) -> Result<(), Box<dyn Error>> {
+    let single_output: bool = match command {
+        Commands::Encode(args) => args.output.output_filename.is_some(),
+        Commands::Remove(args) => args.output.output_filename.is_some(),
+        Commands::InsertChunk(args) => args.output.output_filename.is_some(),
+        Commands::Strip(args) => args.output.output_filename.is_some(),
+        Commands::Repair(args) => args.output.output_filename.is_some(),
+        Commands::Decode(args) => args.output_filename.is_some(),
+        Commands::ExtractChunk(_) => true,
+        Commands::Diff(_) => return Err("diff compares two files, it cannot run on several".into()),
+        _ => false,
+    };
+    if single_output {
+        return Err("--output needs a single input file, outputs are named after each input otherwise".into());
+    }
+    let stdin_input: bool = match command {
+        Commands::Encode(args) => args.message_file.as_deref().is_some_and(is_stdio),
+        Commands::InsertChunk(args) => is_stdio(&args.chunk_filename),
+        _ => false,
+    };
+    if stdin_input {
+        return Err("stdin can only be read once, it cannot be used with several input files".into());
+    }
+    Ok(())
+}
+
+// Reads a PNG file, or stdin for `-`, reporting on stderr any damage a lenient parse worked around
+fn read_png(path: &Path, mode: ParseMode) -> Result<Png, Box<dyn Error>> {
+    let png: Png = if is_stdio(path) {
+        Png::from_reader(io::stdin().lock(), mode)?
+    } else {
+        Png::from_file_with_mode(path, mode)?
+    };
+    for issue in png.issues() {
+        eprintln!("Warning: {}: {}", path.display(), issue);
+    }
+    Ok(png)
+}
+
+// Reads the passphrase from a file, the terminal or the environment, in that order
+fn passphrase(args: &PassphraseArgs) -> Result<Option<String>, Box<dyn Error>> {
+    let passphrase: String = if let Some(passphrase_file) = &args.passphrase_file {
+        let content: String = fs::read_to_string(passphrase_file)
+            .map_err(|error: io::Error| format!("Cannot read {}: {}", passphrase_file.display(), error))?;
+        content.lines().next().unwrap_or_default().to_string()
+    } else if args.ask_passphrase {
+        rpassword::prompt_password("Passphrase: ")?
+    } else {
+        match env::var(PASSPHRASE_VARIABLE) {
+            Ok(passphrase) => passphrase,
+            Err(env::VarError::NotPresent) => return Ok(None),
+            Err(error) => return Err(format!("Invalid {}: {}", PASSPHRASE_VARIABLE, error).into()),
+        }
+    };
+    if passphrase.is_empty() {
+        return Err("The passphrase cannot be empty".into());
+    }
+    Ok(Some(passphrase))
+}
+
+// Parses a chunk type given on the command line
+fn chunk_type(chunk_type: &str) -> Result<ChunkType, Box<dyn Error>> {
+    ChunkType::from_str(chunk_type)
+        .map_err(|_| format!("Invalid chunk type: {}, chunk types are made of 4 ASCII letters", chunk_type).into())
+}
+
+// Looks up a hiding method by name, the chunk method using the given chunk type
+fn select_carrier(method: &str, chunk_type_name: Option<&str>) -> Result<Arc<dyn Carrier>, Box<dyn Error>> {
+    let chunk_type: Option<ChunkType> = match (method, chunk_type_name) {
+        ("chunk", Some(chunk_type_name)) => Some(chunk_type(chunk_type_name)?),
+        ("chunk", None) => return Err("The chunk method needs a chunk type, given with --chunk-type".into()),
+        _ => None,
+    };
+    carrier::select(method, chunk_type)
+}
+
+// Warns on stderr about the chunks dropped by a method rewriting the image data
+fn warn_unsafe_to_copy(method: &dyn Carrier, png: &Png) {
+    for warning in report::unsafe_to_copy_warnings(method, png) {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+// Returns the chunk picked by an index or a type, along with its index
+fn select_chunk(png: &Png, selector: &ChunkSelector) -> Result<(usize, Chunk), Box<dyn Error>> {
+    let chunks: Vec<Chunk> = png.chunks();
+    let index: usize = match (selector.index, &selector.chunk_type) {
+        (Some(index), _) if index < chunks.len() => index,
+        (Some(index), _) => return Err(format!("No chunk at index {} ({} chunks)", index, chunks.len()).into()),
+        (None, Some(chunk_type)) => chunks
+            .iter()
+            .position(|chunk: &Chunk| chunk.chunk_type().to_string() == *chunk_type)
+            .ok_or_else(|| format!("No {} chunk found", chunk_type))?,
+        (None, None) => return Err("Either a chunk index or a chunk type is required".into()),
+    };
+    Ok((index, chunks[index].clone()))
+}
+
+
+// Stream for status messages: `out`, unless stdout already carries the output data
+fn report_stream(out: &mut dyn Write, stdout_taken: bool) -> Box<dyn Write + '_> {
+    if stdout_taken { Box::new(io::stderr()) } else { Box::new(out) }
+}
+
+// Reports what a mutating command would change without writing anything. Fails like the
+// actual write would if the destination cannot be overwritten.
+fn dry_run(
+    out: &mut dyn Write, format: OutputFormat, args: &OutputArgs, output_filename: &Path, before: &Png, after: &Png
+) -> Result<(), Box<dyn Error>> {
+    output::check_destination(args, output_filename)?;
+    report::dry_run(out, format, output_filename, before, after)
+}
@@ -0,0 +1,196 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use img_msg_encoder::report::is_stdio;
+
+use crate::commands::OutputArgs;
+
+// Resolves where a command reading `input` writes the PNG it produces. Without `--output` nor
+// `--in-place`, a file named after the input and the suffix is created next to it, e.g.
+// `image.encoded.png`, while a PNG read from stdin goes to stdout.
+pub fn destination(args: &OutputArgs, input: &Path, suffix: &str) -> PathBuf {
+    if args.in_place {
+        return input.to_path_buf();
+    }
+    if let Some(output_filename) = &args.output_filename {
+        return output_filename.to_path_buf();
+    }
+    if is_stdio(input) {
+        return PathBuf::from("-");
+    }
+    let mut filename: OsString = input.file_stem().unwrap_or_default().to_os_string();
+    filename.push(format!(".{}.png", suffix));
+    input.with_file_name(filename)
+}
+
+// Fails if the PNG produced by a command could not be written to its destination
+pub fn check_destination(args: &OutputArgs, path: &Path) -> Result<(), Box<dyn Error>> {
+    check_overwrite(path, args.force || args.in_place)
+}
+
+// Writes the PNG produced by a command to its destination, following the output flags
+pub fn write_png(args: &OutputArgs, path: &Path, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    write(path, bytes, args.force || args.in_place, args.backup)
+}
+
+// Writes bytes to a file, or stdout for `-`. An existing file is only replaced when `overwrite`
+// is set, after being copied to `<path>.bak` when `backup` is set. The file is replaced
+// atomically: the bytes are written to a temporary file in the same directory, then renamed,
+// or linked when the file must not be overwritten.
+pub fn write(path: &Path, bytes: &[u8], overwrite: bool, backup: bool) -> Result<(), Box<dyn Error>> {
+    if is_stdio(path) {
+        let mut stdout: io::StdoutLock = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    check_overwrite(path, overwrite)?;
+    let existing: Option<fs::Metadata> = fs::metadata(path).ok();
+    if existing.is_some() && backup {
+        fs::copy(path, backup_path(path))?;
+    }
+
+    let temporary: PathBuf = temporary_path(path);
+    let result: Result<(), Box<dyn Error>> = (|| {
+        let mut file: fs::File = fs::File::create(&temporary)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        if let Some(metadata) = &existing {
+            fs::set_permissions(&temporary, metadata.permissions())?;
+        }
+        if overwrite {
+            fs::rename(&temporary, path)?;
+            return Ok(());
+        }
+        // Unlike a rename, linking fails if the file was created since it was checked
+        let published: io::Result<()> = match fs::hard_link(&temporary, path) {
+            // FAT reports EPERM rather than EOPNOTSUPP for hard links
+            Err(err) if matches!(err.kind(), io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied) => {
+                copy_new(&temporary, path)
+            }
+            linked => linked,
+        };
+        if let Err(err) = published {
+            if err.kind() == io::ErrorKind::AlreadyExists {
+                return Err(format!("{} already exists, use --force to overwrite it", path.display()).into());
+            }
+            return Err(err.into());
+        }
+        fs::remove_file(&temporary)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result.map_err(|err| format!("Could not write {}: {}", path.display(), err).into())
+}
+
+// Copies a file to a path which must not exist yet, for filesystems without hard links.
+// The copy is removed if it could not be completed.
+fn copy_new(from: &Path, to: &Path) -> io::Result<()> {
+    let mut file: fs::File = fs::OpenOptions::new().write(true).create_new(true).open(to)?;
+    let result: io::Result<()> = (|| {
+        io::copy(&mut fs::File::open(from)?, &mut file)?;
+        fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+        file.sync_all()
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(to);
+    }
+    result
+}
+
+// Fails if the path is taken by a directory, or by a file which cannot be overwritten
+fn check_overwrite(path: &Path, overwrite: bool) -> Result<(), Box<dyn Error>> {
+    if is_stdio(path) {
+        return Ok(());
+    }
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Err(format!("{} is a directory", path.display()).into()),
+        Ok(_) if !overwrite => Err(format!("{} already exists, use --force to overwrite it", path.display()).into()),
+        _ => Ok(()),
+    }
+}
+
+// Path of the copy made of a file before overwriting it
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup: OsString = path.as_os_str().to_os_string();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+// Path of the temporary file written before replacing a file, hidden in the same directory
+// so that the final rename stays on the same filesystem
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut filename: OsString = OsString::from(".");
+    filename.push(path.file_name().unwrap_or_default());
+    filename.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(filename)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use crate::commands::OutputArgs;
+    use crate::output;
+
+    fn output_args() -> OutputArgs {
+        OutputArgs{output_filename: None, in_place: false, force: false, backup: false, dry_run: false}
+    }
+
+    #[test]
+    fn test_destination() {
+        let input: &Path = Path::new("images/cat.png");
+        assert_eq!(output::destination(&output_args(), input, "encoded"), Path::new("images/cat.encoded.png"));
+        assert_eq!(output::destination(&output_args(), Path::new("-"), "encoded"), Path::new("-"));
+
+        let in_place: OutputArgs = OutputArgs{in_place: true, ..output_args()};
+        assert_eq!(output::destination(&in_place, input, "encoded"), input);
+        let explicit: OutputArgs = OutputArgs{output_filename: Some(PathBuf::from("out.png")), ..output_args()};
+        assert_eq!(output::destination(&explicit, input, "encoded"), Path::new("out.png"));
+    }
+
+    #[test]
+    fn test_write() {
+        let directory: PathBuf = std::env::temp_dir().join(format!("img_msg_encoder_output_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path: PathBuf = directory.join("image.png");
+
+        output::write(&path, b"first", false, false).unwrap();
+        assert!(output::write(&path, b"second", false, true).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+
+        output::write(&path, b"second", true, true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(fs::read(directory.join("image.png.bak")).unwrap(), b"first");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_copy_new() {
+        let directory: PathBuf = std::env::temp_dir().join(format!("img_msg_encoder_copy_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let temporary: PathBuf = directory.join(".image.png.tmp");
+        let path: PathBuf = directory.join("image.png");
+        fs::write(&temporary, b"first").unwrap();
+
+        output::copy_new(&temporary, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        fs::write(&temporary, b"second").unwrap();
+        let err: io::Error = output::copy_new(&temporary, &path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
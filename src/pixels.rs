@@ -0,0 +1,326 @@
+use std::error::Error;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// The content of an IHDR chunk, describing the image data.
+/// See http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub interlace_method: u8,
+}
+impl ImageHeader {
+    // Reads the IHDR chunk of a PNG
+    pub fn from_png(png: &Png) -> Result<ImageHeader, Box<dyn Error>> {
+        let chunk: &Chunk = png.chunk_by_type("IHDR").ok_or("No IHDR chunk found")?;
+        ImageHeader::try_from(chunk.data())
+    }
+
+    // Number of samples per pixel
+    pub fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+    // Name of the color type, as in the spec
+    pub fn color_type_name(&self) -> &str {
+        match self.color_type {
+            0 => "grayscale",
+            2 => "truecolor",
+            3 => "indexed-color",
+            4 => "grayscale with alpha",
+            _ => "truecolor with alpha",
+        }
+    }
+    // Whether the last sample of each pixel is an alpha sample
+    pub fn has_alpha(&self) -> bool {
+        self.color_type == 4 || self.color_type == 6
+    }
+    // Number of bits per pixel
+    pub fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+    // Number of bytes per scanline, without the filter type byte
+    pub fn row_length(&self) -> Result<usize, Box<dyn Error>> {
+        let bits: usize = (self.width as usize)
+            .checked_mul(self.bits_per_pixel())
+            .ok_or_else(|| format!("Image too large ({} pixels wide)", self.width))?;
+        Ok(bits.div_ceil(8))
+    }
+    // Number of bytes of the filtered scanlines, each starting with its filter type byte
+    pub fn filtered_length(&self) -> Result<usize, Box<dyn Error>> {
+        self.row_length()?
+            .checked_add(1)
+            .and_then(|length: usize| length.checked_mul(self.height as usize))
+            .ok_or_else(|| format!("Image too large ({}x{})", self.width, self.height).into())
+    }
+}
+impl TryFrom<&[u8]> for ImageHeader {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() != 13 {
+            return Err(format!("Invalid IHDR chunk (13 bytes expected, {} found)", bytes.len()).into());
+        }
+        let header: ImageHeader = ImageHeader{
+            width: u32::from_be_bytes(bytes[0..4].try_into()?),
+            height: u32::from_be_bytes(bytes[4..8].try_into()?),
+            bit_depth: bytes[8],
+            color_type: bytes[9],
+            interlace_method: bytes[12],
+        };
+        if header.width == 0 || header.height == 0 {
+            return Err(format!("Invalid IHDR chunk (empty image: {}x{})", header.width, header.height).into());
+        }
+        let valid_depths: &[u8] = match header.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(format!("Invalid IHDR chunk (unknown color type: {})", header.color_type).into()),
+        };
+        if !valid_depths.contains(&header.bit_depth) {
+            return Err(format!(
+                "Invalid IHDR chunk (bit depth {} isn't allowed for color type {})",
+                header.bit_depth, header.color_type
+            ).into());
+        }
+        Ok(header)
+    }
+}
+
+/// Decoded image data: the unfiltered scanlines, along with the filter type of each one so that
+/// the image can be encoded back with a similar size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixels {
+    header: ImageHeader,
+    filter_types: Vec<u8>,
+    data: Vec<u8>,
+}
+impl Pixels {
+    // Decodes the concatenated IDAT chunks of a PNG
+    pub fn from_png(png: &Png) -> Result<Pixels, Box<dyn Error>> {
+        let header: ImageHeader = ImageHeader::from_png(png)?;
+        let compressed: Vec<u8> = png.chunks()
+            .iter()
+            .filter(|chunk: &&Chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|chunk: &Chunk| chunk.data().to_vec())
+            .collect();
+        Pixels::decode(header, &compressed)
+    }
+
+    // Inflates and unfilters a zlib stream of scanlines described by the header
+    pub fn decode(header: ImageHeader, compressed: &[u8]) -> Result<Pixels, Box<dyn Error>> {
+        if header.interlace_method != 0 {
+            return Err("Interlaced images are not supported".into());
+        }
+        let row_length: usize = header.row_length()?;
+        let height: usize = header.height as usize;
+        let expected_length: usize = header.filtered_length()?;
+        // One byte past the expected length is enough to tell the data is too long, without
+        // inflating a decompression bomb
+        let mut filtered: Vec<u8> = vec![];
+        ZlibDecoder::new(compressed).take(expected_length as u64 + 1).read_to_end(&mut filtered)?;
+        if filtered.len() > expected_length {
+            return Err(format!("Invalid image data (more than the {} bytes expected)", expected_length).into());
+        }
+        if filtered.len() < expected_length {
+            return Err(format!(
+                "Invalid image data ({} bytes expected, {} found)",
+                expected_length, filtered.len()
+            ).into());
+        }
+
+        let bpp: usize = header.bits_per_pixel().div_ceil(8);
+        let mut filter_types: Vec<u8> = Vec::with_capacity(height);
+        let mut data: Vec<u8> = Vec::with_capacity(expected_length - height);
+        for row in 0..height {
+            let line: &[u8] = &filtered[(row * (row_length + 1))..((row + 1) * (row_length + 1))];
+            let filter_type: u8 = line[0];
+            let start: usize = data.len();
+            for i in 0..row_length {
+                let (a, b, c) = neighbours(&data, start, i, bpp, row_length, row > 0);
+                data.push(line[i + 1].wrapping_add(predict(filter_type, a, b, c)?));
+            }
+            filter_types.push(filter_type);
+        }
+
+        Ok(Pixels{header, filter_types, data})
+    }
+
+    // Filters back the scanlines with their original filter types, then deflates them
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let row_length: usize = self.header.row_length()?;
+        let bpp: usize = self.header.bits_per_pixel().div_ceil(8);
+        let mut filtered: Vec<u8> = Vec::with_capacity(self.data.len() + self.filter_types.len());
+        for (row, filter_type) in self.filter_types.iter().enumerate() {
+            let start: usize = row * row_length;
+            filtered.push(*filter_type);
+            for i in 0..row_length {
+                let (a, b, c) = neighbours(&self.data, start, i, bpp, row_length, row > 0);
+                filtered.push(self.data[start + i].wrapping_sub(predict(*filter_type, a, b, c)?));
+            }
+        }
+
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::best());
+        encoder.write_all(&filtered)?;
+        Ok(encoder.finish()?)
+    }
+
+    // Replaces the IDAT chunks of a PNG with a single one holding these pixels, dropping the
+    // unregistered ancillary chunks which aren't safe to copy once image data changes
+    pub fn write_to(&self, png: &mut Png) -> Result<(), Box<dyn Error>> {
+        let index: usize = png.chunks()
+            .iter()
+            .position(|chunk: &Chunk| chunk.chunk_type().bytes() == *b"IDAT")
+            .ok_or("No IDAT chunk found")?;
+        png.remove_all_chunks("IDAT");
+        png.insert_chunk(index, Chunk::new(*b"IDAT", self.encode()?));
+        png.strip_unsafe_to_copy();
+        Ok(())
+    }
+
+    // The image header these pixels follow
+    pub fn header(&self) -> &ImageHeader {
+        &self.header
+    }
+    // The unfiltered scanlines, concatenated
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    // The unfiltered scanlines, concatenated, for edition
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    // Offsets in `data` of the bytes holding the least significant bit of each color sample,
+    // leaving alpha samples out. Only 8 and 16-bit non-palette images have such bytes.
+    pub fn color_sample_offsets(&self) -> Result<Vec<usize>, Box<dyn Error>> {
+        if self.header.color_type == 3 || self.header.bit_depth < 8 {
+            return Err(format!(
+                "Only 8 and 16-bit grayscale or truecolor images are supported (color type {}, bit depth {})",
+                self.header.color_type, self.header.bit_depth
+            ).into());
+        }
+        let sample_size: usize = self.header.bit_depth as usize / 8;
+        let channels: usize = self.header.channels();
+        let color_channels: usize = if self.header.has_alpha() { channels - 1 } else { channels };
+        Ok((0..(self.data.len() / sample_size))
+            .filter(|sample: &usize| sample % channels < color_channels)
+            .map(|sample: usize| sample * sample_size + sample_size - 1)
+            .collect())
+    }
+}
+
+// Returns the bytes left, above, and above left of the i-th byte of a scanline, as defined by
+// the PNG filters
+fn neighbours(data: &[u8], start: usize, i: usize, bpp: usize, row_length: usize, has_previous: bool) -> (u8, u8, u8) {
+    let a: u8 = if i >= bpp { data[start + i - bpp] } else { 0 };
+    let b: u8 = if has_previous { data[start + i - row_length] } else { 0 };
+    let c: u8 = if has_previous && i >= bpp { data[start + i - row_length - bpp] } else { 0 };
+    (a, b, c)
+}
+
+// Returns the value predicted by a filter type from the neighbouring bytes
+fn predict(filter_type: u8, a: u8, b: u8, c: u8) -> Result<u8, Box<dyn Error>> {
+    match filter_type {
+        0 => Ok(0),
+        1 => Ok(a),
+        2 => Ok(b),
+        3 => Ok(((a as u16 + b as u16) / 2) as u8),
+        4 => {
+            let p: i16 = a as i16 + b as i16 - c as i16;
+            let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+            if pa <= pb && pa <= pc {
+                Ok(a)
+            } else if pb <= pc {
+                Ok(b)
+            } else {
+                Ok(c)
+            }
+        }
+        _ => Err(format!("Invalid image data (unknown filter type: {})", filter_type).into()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use crate::chunk::Chunk;
+    use crate::pixels::{ImageHeader, Pixels};
+    use crate::png::Png;
+
+    // A 3x2 RGB image whose rows use the Sub and Paeth filters
+    fn testing_png() -> Png {
+        let filtered: Vec<u8> = vec![
+            1, 10, 20, 30, 5, 5, 5, 5, 5, 5,
+            4, 1, 1, 1, 0, 0, 0, 2, 2, 2,
+        ];
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]),
+            Chunk::new(*b"IDAT", encoder.finish().unwrap()),
+            Chunk::new(*b"IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_header() {
+        let header: ImageHeader = ImageHeader::from_png(&testing_png()).unwrap();
+        assert_eq!((header.width, header.height), (3, 2));
+        assert_eq!(header.channels(), 3);
+        assert_eq!(header.row_length().unwrap(), 9);
+        assert!(ImageHeader::try_from(&[0, 0, 0, 3, 0, 0, 0, 2, 4, 2, 0, 0, 0][..]).is_err());
+        assert!(ImageHeader::try_from(&[0, 0, 0, 0, 0, 0, 0, 2, 8, 2, 0, 0, 0][..]).is_err());
+        assert!(ImageHeader::try_from(&[0, 0, 0, 3, 0, 0, 0, 0, 8, 2, 0, 0, 0][..]).is_err());
+    }
+    #[test]
+    fn test_decode_wrong_length() {
+        let header: ImageHeader = ImageHeader::from_png(&testing_png()).unwrap();
+        for length in [19, 21] {
+            let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(&vec![0; length]).unwrap();
+            assert!(Pixels::decode(header, &encoder.finish().unwrap()).is_err());
+        }
+
+        let header: ImageHeader = ImageHeader{width: u32::MAX, height: u32::MAX, ..header};
+        assert!(Pixels::decode(header, &[]).is_err());
+    }
+    #[test]
+    fn test_decode() {
+        let pixels: Pixels = Pixels::from_png(&testing_png()).unwrap();
+        assert_eq!(pixels.data(), &[
+            10, 20, 30, 15, 25, 35, 20, 30, 40,
+            11, 21, 31, 15, 25, 35, 22, 32, 42,
+        ]);
+    }
+    #[test]
+    fn test_round_trip() {
+        let mut png: Png = testing_png();
+        let mut pixels: Pixels = Pixels::from_png(&png).unwrap();
+        pixels.data_mut()[4] ^= 1;
+        pixels.write_to(&mut png).unwrap();
+
+        assert_eq!(Pixels::from_png(&png).unwrap(), pixels);
+    }
+    #[test]
+    fn test_color_sample_offsets() {
+        let pixels: Pixels = Pixels::from_png(&testing_png()).unwrap();
+        assert_eq!(pixels.color_sample_offsets().unwrap().len(), 18);
+    }
+}
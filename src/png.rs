@@ -1,14 +1,44 @@
 use std::error::Error;
 use std::{fmt, fs};
+use std::io::Read;
+use std::ops::RangeInclusive;
 use std::path::Path;
 use rand::Rng;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::pixels::ImageHeader;
+use crate::registry;
+
+/// How strictly bytes are checked when parsing a PNG
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    // Any invalid byte makes the whole parse fail
+    #[default]
+    Strict,
+    // Invalid chunks are kept and marked as damaged, unreadable bytes are skipped
+    Lenient,
+}
+
+/// A problem a lenient parse worked around, located by its byte offset in the file
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ParseIssue {
+    pub offset: usize,
+    pub description: String,
+}
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.description)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailing_data: Vec<u8>,
+    issues: Vec<ParseIssue>,
 }
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
@@ -17,15 +47,34 @@ impl Png {
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
         Png{
             header: Self::STANDARD_HEADER,
-            chunks
+            chunks,
+            trailing_data: vec![],
+            issues: vec![],
         }
     }
     // Creates a PNG from a file path
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_file_with_mode(path, ParseMode::Strict)
+    }
+    // Creates a PNG from a file path, parsing it with the given mode
+    pub fn from_file_with_mode<P: AsRef<Path>>(path: P, mode: ParseMode) -> Result<Self, Box<dyn Error>> {
         let f: Vec<u8> = fs::read(path)?;
-        let png: Self = Self::try_from(&f[..])?;
+        let png: Self = Self::parse(&f[..], mode)?;
         Ok(png)
     }
+    // Creates a PNG from a reader such as stdin, parsing it with the given mode once fully read
+    pub fn from_reader<R: Read>(mut reader: R, mode: ParseMode) -> Result<Self, Box<dyn Error>> {
+        let mut bytes: Vec<u8> = vec![];
+        reader.read_to_end(&mut bytes)?;
+        Self::parse(&bytes, mode)
+    }
+    // Creates a PNG from bytes, parsing them with the given mode
+    pub fn parse(bytes: &[u8], mode: ParseMode) -> Result<Self, Box<dyn Error>> {
+        match mode {
+            ParseMode::Strict => Self::try_from(bytes),
+            ParseMode::Lenient => Self::parse_lenient(bytes),
+        }
+    }
 
     // This PNG header
     pub fn header(&self) -> &[u8; 8] {
@@ -35,6 +84,33 @@ impl Png {
     pub fn chunks(&self) -> Vec<Chunk> {
         self.chunks.to_vec()
     }
+    // Bytes found after the IEND chunk, or which couldn't be read as chunks by a lenient parse
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+    // Replaces the bytes written after the last chunk
+    pub fn set_trailing_data(&mut self, trailing_data: Vec<u8>) {
+        self.trailing_data = trailing_data;
+    }
+    // Problems worked around while parsing this PNG, always empty after a strict parse
+    pub fn issues(&self) -> &[ParseIssue] {
+        &self.issues
+    }
+    // Byte offset in the file of each chunk, which takes 12 bytes on top of its data
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        self.chunks
+            .iter()
+            .scan(self.header.len(), |offset: &mut usize, chunk: &Chunk| {
+                let current: usize = *offset;
+                *offset += 12 + chunk.length() as usize;
+                Some(current)
+            })
+            .collect()
+    }
+    // Whether this PNG has been found damaged by a lenient parse
+    pub fn is_damaged(&self) -> bool {
+        !self.issues.is_empty() || self.chunks.iter().any(|chunk: &Chunk| chunk.is_damaged())
+    }
 
     // Returns the first chunk with the specified chunk_type from the PNG
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
@@ -52,9 +128,43 @@ impl Png {
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.append(&mut vec![chunk])
     }
-    // Appends a chunk somewhere in the chunks list
+    // Appends a chunk somewhere in the chunks list, between IHDR and IEND, without splitting a
+    // run of data chunks or an APNG frame from its data
     pub fn append_chunk_somewhere(&mut self, chunk: Chunk){
-        self.chunks.insert(rand::thread_rng().gen_range(0..self.chunks.len()), chunk);
+        let positions: Vec<usize> = self.safe_insertion_positions();
+        self.chunks.insert(positions[rand::thread_rng().gen_range(0..positions.len())], chunk);
+    }
+    // Inserts a chunk at the given position in the chunks list
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk);
+    }
+    // Positions where a chunk can be inserted without moving IHDR from the front or IEND from
+    // the end of the chunks list
+    pub fn insertion_range(&self) -> RangeInclusive<usize> {
+        let start: usize = match self.chunks.first() {
+            Some(chunk) if chunk.chunk_type().bytes() == *b"IHDR" => 1,
+            _ => 0,
+        };
+        let end: usize = self.chunks.iter()
+            .position(|chunk: &Chunk| chunk.chunk_type().bytes() == *b"IEND")
+            .unwrap_or(self.chunks.len());
+        start..=end.max(start)
+    }
+    // Positions of the insertion range which aren't between a data chunk (IDAT or fdAT) and the
+    // data or frame control chunk before it. Never empty, as the end of the range is always safe.
+    pub fn safe_insertion_positions(&self) -> Vec<usize> {
+        let type_at = |index: usize| -> Option<[u8; 4]> {
+            self.chunks.get(index).map(|chunk: &Chunk| chunk.chunk_type().bytes())
+        };
+        self.insertion_range()
+            .filter(|index: &usize| {
+                let splits_data: bool = matches!(type_at(*index).as_ref(), Some(b"IDAT" | b"fdAT"))
+                    && index.checked_sub(1).and_then(type_at).is_some_and(|previous: [u8; 4]| {
+                        matches!(&previous, b"IDAT" | b"fdAT" | b"fcTL")
+                    });
+                !splits_data
+            })
+            .collect()
     }
     // Removes the first chunk with the specified chunk_type from the png
     pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Box<dyn Error>> {
@@ -66,13 +176,301 @@ impl Png {
         }
     }
 
+    // Removes all the chunks with the specified chunk_type from the png, returning them in order
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.retain_chunks(|chunk: &Chunk| chunk.chunk_type().to_string() != chunk_type)
+    }
+    // Keeps only the chunks for which `keep` returns true, returning the removed ones in order.
+    // The trailing data and the parse issues are left as they are.
+    pub fn retain_chunks<F: FnMut(&Chunk) -> bool>(&mut self, mut keep: F) -> Vec<Chunk> {
+        let (kept, removed): (Vec<Chunk>, Vec<Chunk>) = self.chunks
+            .drain(..)
+            .partition(|chunk: &Chunk| keep(chunk));
+        self.chunks = kept;
+        removed
+    }
+    // Removes the unknown ancillary chunks which aren't safe to copy, returning them in order.
+    // The spec requires dropping them once critical chunks are changed, as they may depend on
+    // them. Registered chunks such as tRNS or acTL are understood, and kept.
+    pub fn strip_unsafe_to_copy(&mut self) -> Vec<Chunk> {
+        self.retain_chunks(|chunk: &Chunk| {
+            let chunk_type: &ChunkType = chunk.chunk_type();
+            chunk_type.is_critical() || chunk_type.is_safe_to_copy() || registry::is_registered(chunk_type)
+        })
+    }
+    // Parses the animation of an APNG, `None` for a still image without acTL chunk. Data
+    // chunks belong to the last frame control chunk before them. Frames must be within the
+    // canvas given by IHDR.
+    pub fn animation(&self) -> Result<Option<Animation>, Box<dyn Error>> {
+        let Some(animation) = self.parse_animation()? else {
+            return Ok(None);
+        };
+        let header: ImageHeader = ImageHeader::from_png(self)?;
+        if let Some(frame) = animation.frames.iter().find(|frame: &&Frame| !frame.control.fits(header.width, header.height)) {
+            return Err(frame_outside_canvas(frame, &header).into());
+        }
+        Ok(Some(animation))
+    }
+    // Parses the animation of an APNG without checking the geometry of its frames
+    fn parse_animation(&self) -> Result<Option<Animation>, Box<dyn Error>> {
+        let Some(chunk) = self.chunk_by_type("acTL") else {
+            return Ok(None);
+        };
+        let control: AnimationControl = AnimationControl::try_from(chunk.data())?;
+        let mut frames: Vec<Frame> = vec![];
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            match &chunk.chunk_type().bytes() {
+                b"fcTL" => frames.push(Frame{
+                    control: FrameControl::try_from(chunk.data())?,
+                    control_index: index,
+                    data_indexes: vec![],
+                    default_image: false,
+                }),
+                chunk_type @ (b"IDAT" | b"fdAT") => {
+                    if let Some(frame) = frames.last_mut() {
+                        if frame.data_indexes.is_empty() {
+                            frame.default_image = chunk_type == b"IDAT";
+                        }
+                        frame.data_indexes.push(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(Animation{control, frames}))
+    }
+    // The compressed data of a frame, concatenated from its chunks without sequence numbers
+    pub fn frame_data(&self, frame: &Frame) -> Vec<u8> {
+        frame.data_indexes
+            .iter()
+            .filter_map(|index: &usize| self.chunks.get(*index))
+            .flat_map(|chunk: &Chunk| match &chunk.chunk_type().bytes() {
+                b"fdAT" => chunk.data().get(4..).unwrap_or_default().to_vec(),
+                _ => chunk.data().to_vec(),
+            })
+            .collect()
+    }
+    // Replaces the data chunks of a frame with a single one holding the compressed data, then
+    // renumbers the sequence. Chunk indexes of the frames after this one become stale.
+    pub fn set_frame_data(&mut self, frame: &Frame, compressed: &[u8]) -> Result<(), Box<dyn Error>> {
+        let first: usize = *frame.data_indexes.first().ok_or("The frame has no data chunk")?;
+        for index in frame.data_indexes.iter().rev() {
+            self.chunks.remove(*index);
+        }
+        let chunk: Chunk = if frame.default_image {
+            Chunk::new(*b"IDAT", compressed.to_vec())
+        } else {
+            Chunk::new(*b"fdAT", [0; 4].iter().chain(compressed.iter()).copied().collect())
+        };
+        self.chunks.insert(first, chunk);
+        self.renumber_sequence();
+        Ok(())
+    }
+    // Replaces the frame control chunk of a frame, keeping its sequence number
+    pub fn set_frame_control(&mut self, frame: &Frame, control: FrameControl) {
+        let sequence_number: u32 = frame.control.sequence_number;
+        self.chunks[frame.control_index] = Chunk::new(*b"fcTL", FrameControl{sequence_number, ..control}.to_bytes());
+    }
+    // Numbers the fcTL and fdAT chunks from 0 in the order they appear, as players require
+    pub fn renumber_sequence(&mut self) {
+        let mut sequence_number: u32 = 0;
+        for chunk in self.chunks.iter_mut() {
+            if !matches!(&chunk.chunk_type().bytes(), b"fcTL" | b"fdAT") || chunk.data().len() < 4 {
+                continue;
+            }
+            let mut data: Vec<u8> = chunk.data().to_vec();
+            data[0..4].copy_from_slice(&sequence_number.to_be_bytes());
+            *chunk = Chunk::new(chunk.chunk_type().bytes(), data);
+            sequence_number += 1;
+        }
+    }
+    // Lists the problems making players reject an animation or play it wrongly: unreadable
+    // control chunks, broken sequence numbering, frame count mismatches, frames without data
+    // and frames outside the canvas. Always empty for still images.
+    pub fn check_sequence(&self) -> Vec<String> {
+        let animation: Animation = match self.parse_animation() {
+            Ok(Some(animation)) => animation,
+            Ok(None) if self.chunk_by_type("fcTL").is_some() || self.chunk_by_type("fdAT").is_some() => {
+                return vec![String::from("fcTL or fdAT chunks found without an acTL chunk")];
+            }
+            Ok(None) => return vec![],
+            Err(e) => return vec![e.to_string()],
+        };
+
+        let mut problems: Vec<String> = vec![];
+        let mut expected: u32 = 0;
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let chunk_type: [u8; 4] = chunk.chunk_type().bytes();
+            if !matches!(&chunk_type, b"fcTL" | b"fdAT") {
+                continue;
+            }
+            if &chunk_type == b"fdAT" && animation.frames.first().is_none_or(|frame: &Frame| frame.control_index > index) {
+                problems.push(format!("fdAT chunk {} comes before any fcTL chunk", index));
+            }
+            let Some(bytes) = chunk.data().get(0..4) else {
+                problems.push(format!("fdAT chunk {} is too short to hold a sequence number", index));
+                continue;
+            };
+            let sequence_number: u32 = u32::from_be_bytes(bytes.try_into().unwrap());
+            if sequence_number != expected {
+                problems.push(format!(
+                    "{} chunk {} has sequence number {} instead of {}",
+                    chunk.chunk_type(), index, sequence_number, expected
+                ));
+            }
+            expected += 1;
+        }
+
+        if animation.control.num_frames as usize != animation.frames.len() {
+            problems.push(format!(
+                "acTL chunk announces {} frames, {} fcTL chunks found",
+                animation.control.num_frames, animation.frames.len()
+            ));
+        }
+        for (number, frame) in animation.frames.iter().enumerate() {
+            if frame.data_indexes.is_empty() {
+                problems.push(format!("frame {} (fcTL chunk {}) has no data", number, frame.control_index));
+            }
+        }
+        match ImageHeader::from_png(self) {
+            Ok(header) => problems.extend(animation.frames
+                .iter()
+                .filter(|frame: &&Frame| !frame.control.fits(header.width, header.height))
+                .map(|frame: &Frame| frame_outside_canvas(frame, &header))),
+            Err(e) => problems.push(e.to_string()),
+        }
+        problems
+    }
+
     // Returns the PNG as a byte sequence
     pub fn as_bytes(&self) -> Vec<u8> {
         // Can it be done with a fold?
         let mut result: Vec<u8> = self.header.to_vec();
         self.chunks.iter().for_each(|chunk: &Chunk| {result.extend(chunk.as_bytes());});
+        result.extend_from_slice(&self.trailing_data);
         result
     }
+
+    // Parses as much as possible of the given bytes. Chunks with a CRC mismatch or an invalid
+    // chunk type are kept as long as their declared length leads to another plausible chunk,
+    // otherwise the parse resynchronizes on the next valid chunk found further in the bytes.
+    fn parse_lenient(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < 8 {
+            return Err(
+                format!("Invalid PNG (PNG total size cannot be inferior to 8 bytes ({}))", bytes.len()).into()
+            );
+        }
+
+        let header: [u8; 8] = bytes[0..8].try_into()?;
+        let mut issues: Vec<ParseIssue> = vec![];
+        if header != Self::STANDARD_HEADER {
+            issues.push(ParseIssue{offset: 0, description: String::from("header is not standard")});
+        }
+
+        let mut cursor: usize = 8;
+        let mut chunks: Vec<Chunk> = vec![];
+        let mut trailing_data: Vec<u8> = vec![];
+        while cursor < bytes.len() {
+            let chunk: Option<Chunk> = match Chunk::try_from(&bytes[cursor..]) {
+                Ok(chunk) => Some(chunk),
+                Err(_) => Self::damaged_chunk_at(bytes, cursor),
+            };
+
+            match chunk {
+                Some(chunk) => {
+                    if chunk.is_damaged() {
+                        issues.push(ParseIssue{
+                            offset: cursor,
+                            description: format!(
+                                "damaged {} chunk ({})",
+                                chunk.chunk_type(),
+                                if chunk.is_crc_valid() { "invalid chunk type" } else { "CRC mismatch" }
+                            ),
+                        });
+                    }
+                    cursor += 12 + chunk.length() as usize;
+                    let is_end: bool = chunk.chunk_type().bytes() == *b"IEND";
+                    chunks.push(chunk);
+                    if is_end {
+                        trailing_data = bytes[cursor..].to_vec();
+                        break;
+                    }
+                }
+                None => {
+                    let next: Option<usize> = (cursor + 1..bytes.len()).find(|position: &usize| {
+                        Self::is_plausible_chunk_at(bytes, *position)
+                            && Chunk::try_from(&bytes[*position..]).is_ok()
+                    });
+                    match next {
+                        Some(next) => {
+                            issues.push(ParseIssue{
+                                offset: cursor,
+                                description: format!("skipped {} unreadable bytes", next - cursor),
+                            });
+                            cursor = next;
+                        }
+                        None => {
+                            issues.push(ParseIssue{
+                                offset: cursor,
+                                description: format!("{} unreadable bytes at the end", bytes.len() - cursor),
+                            });
+                            trailing_data = bytes[cursor..].to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Png{
+            header,
+            chunks,
+            trailing_data,
+            issues,
+        })
+    }
+
+    // Reads a chunk failing its CRC or chunk type checks, only if its declared length leads
+    // either to the end of the bytes or to another plausible chunk
+    fn damaged_chunk_at(bytes: &[u8], position: usize) -> Option<Chunk> {
+        if position + 12 > bytes.len() {
+            return None;
+        }
+        let length: usize = u32::from_be_bytes(bytes[position..(position + 4)].try_into().ok()?) as usize;
+        let end: usize = position.checked_add(12 + length)?;
+        if end > bytes.len() || (end < bytes.len() && !Self::is_plausible_chunk_at(bytes, end)) {
+            return None;
+        }
+
+        let chunk_type: [u8; 4] = bytes[(position + 4)..(position + 8)].try_into().ok()?;
+        let crc: u32 = u32::from_be_bytes(bytes[(end - 4)..end].try_into().ok()?);
+        Some(Chunk::from_parts(
+            ChunkType::from_bytes_unchecked(chunk_type),
+            bytes[(position + 8)..(end - 4)].to_vec(),
+            crc
+        ))
+    }
+
+    // Whether the bytes at this position look like the start of a chunk: an alphabetic chunk
+    // type, and a length fitting in the remaining bytes
+    fn is_plausible_chunk_at(bytes: &[u8], position: usize) -> bool {
+        if position + 12 > bytes.len() {
+            return false;
+        }
+        let length: usize = u32::from_be_bytes([
+            bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]
+        ]) as usize;
+        bytes[(position + 4)..(position + 8)].iter().all(|byte: &u8| byte.is_ascii_alphabetic())
+            && length <= bytes.len() - position - 12
+    }
+}
+// Describes a frame which is empty or reaches out of the canvas
+fn frame_outside_canvas(frame: &Frame, header: &ImageHeader) -> String {
+    format!(
+        "fcTL chunk {} describes a {}x{} frame at ({}, {}), outside the {}x{} canvas",
+        frame.control_index, frame.control.width, frame.control.height,
+        frame.control.x_offset, frame.control.y_offset, header.width, header.height
+    )
 }
 impl TryFrom<&[u8]> for Png {
     type Error = Box<dyn Error>;
@@ -98,27 +496,46 @@ impl TryFrom<&[u8]> for Png {
             )
         }
 
-        let mut cursor: u32 = 8;
+        let mut cursor: usize = 8;
         let mut chunks: Vec<Chunk> = vec![];
-        while cursor < bytes.len() as u32 {
-            let current_chunk_length: u32 = u32::from_be_bytes(
-                bytes[(cursor as usize)..((cursor as usize) + 4)].try_into()?
-            );
-
-            chunks.append(&mut vec![Chunk::try_from(
-                &bytes[
-                    (cursor as usize)
-                        ..
-                        ((cursor as usize) + 4 + (current_chunk_length as usize) + 4 + 4)
-                    ]
-            )?]);
-            cursor += 4 + current_chunk_length + 4 + 4;
+        while cursor < bytes.len() {
+            let chunk: Chunk = Chunk::try_from(&bytes[cursor..])?;
+            cursor += 4 + chunk.length() as usize + 4 + 4;
+            let is_end: bool = chunk.chunk_type().bytes() == *b"IEND";
+            chunks.append(&mut vec![chunk]);
+            // Decoders ignore anything after IEND, so don't fail on it
+            if is_end {
+                break;
+            }
         }
 
-        Ok(Png{
-            header,
-            chunks
-        })
+        let mut png: Png = Png::from_chunks(chunks);
+        png.trailing_data = bytes[cursor..].to_vec();
+        Ok(png)
+    }
+}
+// A chunk along with its byte offset in the file
+#[derive(serde::Serialize)]
+struct LocatedChunk<'a> {
+    offset: usize,
+    #[serde(flatten)]
+    chunk: &'a Chunk,
+}
+
+impl Serialize for Png {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let chunks: Vec<LocatedChunk> = self.chunk_offsets()
+            .into_iter()
+            .zip(self.chunks.iter())
+            .map(|(offset, chunk)| LocatedChunk{offset, chunk})
+            .collect();
+
+        let mut state: S::SerializeStruct = serializer.serialize_struct("Png", 4)?;
+        state.serialize_field("standard_header", &(self.header == Png::STANDARD_HEADER))?;
+        state.serialize_field("chunks", &chunks)?;
+        state.serialize_field("trailing_data_length", &self.trailing_data.len())?;
+        state.serialize_field("issues", &self.issues)?;
+        state.end()
     }
 }
 impl fmt::Display for Png {
@@ -135,12 +552,120 @@ impl fmt::Display for Png {
     }
 }
 
+/// The content of an acTL chunk, announcing an animated PNG.
+/// See https://wiki.mozilla.org/APNG_Specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    // Number of times the animation plays, 0 meaning forever
+    pub num_plays: u32,
+}
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() != 8 {
+            return Err(format!("Invalid acTL chunk (8 bytes expected, {} found)", bytes.len()).into());
+        }
+        Ok(AnimationControl{
+            num_frames: u32::from_be_bytes(bytes[0..4].try_into()?),
+            num_plays: u32::from_be_bytes(bytes[4..8].try_into()?),
+        })
+    }
+}
+
+/// The content of an fcTL chunk, describing the region, timing and compositing of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: u8,
+    pub blend_op: u8,
+}
+impl FrameControl {
+    // Whether the frame isn't empty and lies within a canvas of the given size
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.width != 0 && self.height != 0
+            && self.x_offset as u64 + self.width as u64 <= width as u64
+            && self.y_offset as u64 + self.height as u64 <= height as u64
+    }
+    // How long the frame is displayed, in seconds. A zero denominator stands for 100.
+    pub fn delay(&self) -> f64 {
+        let den: u16 = if self.delay_den == 0 { 100 } else { self.delay_den };
+        self.delay_num as f64 / den as f64
+    }
+    // The delay fraction in lowest terms, along with the factor both its terms were multiplied
+    // by. A zero denominator is read as 100 and a zero delay as 0/1.
+    pub fn reduced_delay(&self) -> (u16, u16, u16) {
+        let den: u16 = if self.delay_den == 0 { 100 } else { self.delay_den };
+        let (mut a, mut b) = (self.delay_num, den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        (self.delay_num / a, den / a, a)
+    }
+    // The content of the fcTL chunk
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.sequence_number, self.width, self.height, self.x_offset, self.y_offset]
+            .iter()
+            .flat_map(|value: &u32| value.to_be_bytes())
+            .chain(self.delay_num.to_be_bytes())
+            .chain(self.delay_den.to_be_bytes())
+            .chain([self.dispose_op, self.blend_op])
+            .collect()
+    }
+}
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() != 26 {
+            return Err(format!("Invalid fcTL chunk (26 bytes expected, {} found)", bytes.len()).into());
+        }
+        let word = |start: usize| -> u32 { u32::from_be_bytes(bytes[start..(start + 4)].try_into().unwrap()) };
+        Ok(FrameControl{
+            sequence_number: word(0),
+            width: word(4),
+            height: word(8),
+            x_offset: word(12),
+            y_offset: word(16),
+            delay_num: u16::from_be_bytes(bytes[20..22].try_into()?),
+            delay_den: u16::from_be_bytes(bytes[22..24].try_into()?),
+            dispose_op: bytes[24],
+            blend_op: bytes[25],
+        })
+    }
+}
+
+/// A frame of an animated PNG: its fcTL chunk and the chunks holding its data, located by their
+/// index in the chunks list
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub control_index: usize,
+    pub data_indexes: Vec<usize>,
+    // Whether the frame is the default image held by the IDAT chunks, rather than fdAT chunks
+    pub default_image: bool,
+}
+
+/// The frames of an animated PNG, in playback order
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
 
 #[cfg(test)]
 mod tests {
     use std::error::Error;
     use crate::chunk::Chunk;
-    use crate::png::Png;
+    use crate::png::{Animation, FrameControl, ParseMode, Png};
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, Box<dyn Error>> {
         let data: Vec<u8> = data.bytes().collect();
@@ -162,6 +687,104 @@ mod tests {
         Png::from_chunks(chunks)
     }
 
+    // An APNG of 2 frames, the first one being the default image
+    fn testing_apng() -> Png {
+        let control = |sequence_number: u32| -> Chunk {
+            let control: FrameControl = FrameControl{
+                sequence_number, width: 1, height: 1, x_offset: 0, y_offset: 0,
+                delay_num: 1, delay_den: 10, dispose_op: 0, blend_op: 0,
+            };
+            Chunk::new(*b"fcTL", control.to_bytes())
+        };
+        Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(*b"acTL", vec![0, 0, 0, 2, 0, 0, 0, 0]),
+            control(0),
+            Chunk::new(*b"IDAT", vec![1]),
+            Chunk::new(*b"IDAT", vec![2]),
+            control(1),
+            Chunk::new(*b"fdAT", vec![0, 0, 0, 2, 3]),
+            Chunk::new(*b"fdAT", vec![0, 0, 0, 3, 4]),
+            Chunk::new(*b"IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_animation() {
+        let png: Png = testing_apng();
+        let animation: Animation = png.animation().unwrap().unwrap();
+        assert_eq!(animation.control.num_frames, 2);
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames[0].default_image);
+        assert_eq!(animation.frames[1].data_indexes, vec![6, 7]);
+        assert_eq!(animation.frames[1].control.delay(), 0.1);
+        assert_eq!(animation.frames[1].control.reduced_delay(), (1, 10, 1));
+        let control: FrameControl = FrameControl{delay_num: 30, delay_den: 0, ..animation.frames[1].control};
+        assert_eq!(control.reduced_delay(), (3, 10, 10));
+        let control: FrameControl = FrameControl{delay_num: 0, delay_den: 7, ..control};
+        assert_eq!(control.reduced_delay(), (0, 1, 7));
+        assert_eq!(png.frame_data(&animation.frames[0]), vec![1, 2]);
+        assert_eq!(png.frame_data(&animation.frames[1]), vec![3, 4]);
+        assert!(png.check_sequence().is_empty());
+        assert!(Png::from_chunks(testing_chunks()).animation().unwrap().is_none());
+    }
+    #[test]
+    fn test_check_sequence() {
+        let mut png: Png = testing_apng();
+        png.remove_first_chunk("fdAT").unwrap();
+        png.insert_chunk(2, Chunk::new(*b"fdAT", vec![0, 0, 0, 2, 3]));
+        assert_eq!(png.check_sequence(), vec![
+            "fdAT chunk 2 comes before any fcTL chunk",
+            "fdAT chunk 2 has sequence number 2 instead of 0",
+            "fcTL chunk 3 has sequence number 0 instead of 1",
+            "fcTL chunk 6 has sequence number 1 instead of 2",
+        ]);
+
+        let mut png: Png = testing_apng();
+        png.remove_all_chunks("fdAT");
+        png.renumber_sequence();
+        assert_eq!(png.check_sequence(), vec!["frame 1 (fcTL chunk 5) has no data"]);
+        png.remove_all_chunks("fcTL");
+        assert_eq!(png.check_sequence(), vec!["acTL chunk announces 2 frames, 0 fcTL chunks found"]);
+    }
+    #[test]
+    fn test_frame_outside_canvas() {
+        let mut png: Png = testing_apng();
+        let animation: Animation = png.animation().unwrap().unwrap();
+        let control: FrameControl = FrameControl{x_offset: 1, ..animation.frames[1].control};
+        png.set_frame_control(&animation.frames[1], control);
+        assert!(png.animation().is_err());
+        assert_eq!(png.check_sequence(), vec!["fcTL chunk 5 describes a 1x1 frame at (1, 0), outside the 1x1 canvas"]);
+
+        let control: FrameControl = FrameControl{width: 0, ..animation.frames[1].control};
+        assert!(!control.fits(1, 1));
+        let control: FrameControl = FrameControl{y_offset: u32::MAX, ..animation.frames[1].control};
+        assert!(!control.fits(1, 1));
+        assert!(animation.frames[1].control.fits(1, 1));
+    }
+    #[test]
+    fn test_set_frame_data() {
+        let mut png: Png = testing_apng();
+        let animation: Animation = png.animation().unwrap().unwrap();
+        png.set_frame_data(&animation.frames[1], &[5, 6]).unwrap();
+        png.set_frame_data(&animation.frames[0], &[7]).unwrap();
+        let animation: Animation = png.animation().unwrap().unwrap();
+        assert_eq!(png.frame_data(&animation.frames[0]), vec![7]);
+        assert_eq!(png.frame_data(&animation.frames[1]), vec![5, 6]);
+        assert_eq!(png.chunks().len(), 7);
+        assert!(png.check_sequence().is_empty());
+    }
+    #[test]
+    fn test_append_chunk_somewhere_keeps_frames_together() {
+        let png: Png = testing_apng();
+        assert_eq!(png.safe_insertion_positions(), vec![1, 2, 5, 8]);
+        for _ in 0..20 {
+            let mut png: Png = testing_apng();
+            png.append_chunk_somewhere(Chunk::new(*b"ruSt", vec![]));
+            assert!(png.check_sequence().is_empty());
+            assert_eq!(png.animation().unwrap().unwrap().frames[1].data_indexes.len(), 2);
+        }
+    }
     #[test]
     fn test_from_chunks() {
         let chunks: Vec<Chunk> = testing_chunks();
@@ -225,6 +848,77 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_lenient_crc_mismatch() {
+        let mut bytes: Vec<u8> = testing_png().as_bytes();
+        // Last byte of the "miDl" chunk data
+        let position: usize = 8 + 12 + 20 + 8 + 17;
+        bytes[position] = b'!';
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+        let png: Png = Png::parse(bytes.as_ref(), ParseMode::Lenient).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.is_damaged());
+        assert!(png.chunk_by_type("miDl").unwrap().is_damaged());
+        assert!(!png.chunk_by_type("LASt").unwrap().is_damaged());
+        assert_eq!(png.issues().len(), 1);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+    #[test]
+    fn test_lenient_invalid_chunk_type() {
+        let mut bytes: Vec<u8> = testing_png().as_bytes();
+        bytes[8 + 4] = b'1';
+
+        let png: Png = Png::parse(bytes.as_ref(), ParseMode::Lenient).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunks()[0].is_damaged());
+        assert!(!png.chunks()[0].chunk_type().is_alphabetic());
+    }
+    #[test]
+    fn test_lenient_resynchronization() {
+        let mut bytes: Vec<u8> = testing_png().as_bytes();
+        // Corrupt the length of the first chunk, making it impossible to follow
+        bytes[8] = 0xFF;
+
+        let png: Png = Png::parse(bytes.as_ref(), ParseMode::Lenient).unwrap();
+        let chunk_types: Vec<String> = png.chunks()
+            .iter()
+            .map(|chunk: &Chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(chunk_types, vec!["miDl", "LASt"]);
+        assert_eq!(png.issues()[0].offset, 8);
+    }
+    #[test]
+    fn test_lenient_trailing_data() {
+        let mut bytes: Vec<u8> = Png::try_from(&PNG_FILE[..]).unwrap().as_bytes();
+        bytes.extend_from_slice("hidden after the end".as_bytes());
+
+        let png: Png = Png::parse(bytes.as_ref(), ParseMode::Lenient).unwrap();
+        assert_eq!(png.trailing_data(), "hidden after the end".as_bytes());
+        assert!(!png.is_damaged());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_strict_trailing_data() {
+        let mut bytes: Vec<u8> = PNG_FILE.to_vec();
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let png: Png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.trailing_data(), &[1, 2, 3]);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+    #[test]
+    fn test_append_chunk_somewhere() {
+        let mut png: Png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        assert_eq!(png.insertion_range(), 1..=1);
+        png.append_chunk_somewhere(chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png: Png = testing_png();
@@ -251,6 +945,28 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png: Png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+        let removed: Vec<Chunk> = png.remove_all_chunks("TeSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[1].data_as_string().unwrap(), "Second");
+        assert_eq!(png.chunks().len(), 3);
+    }
+    #[test]
+    fn test_strip_unsafe_to_copy() {
+        let mut png: Png = testing_png();
+        png.append_chunk(chunk_from_strings("abCD", "Unsafe").unwrap());
+        png.append_chunk(chunk_from_strings("ABCD", "Critical").unwrap());
+        png.append_chunk(chunk_from_strings("tRNS", "Registered").unwrap());
+        let removed: Vec<Chunk> = png.strip_unsafe_to_copy();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].data_as_string().unwrap(), "Unsafe");
+        assert_eq!(png.chunks().len(), 5);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png: Result<Png, Box<dyn Error>> = Png::try_from(&PNG_FILE[..]);
@@ -282,6 +998,24 @@ mod tests {
 
         let _png_string: String = format!("{}", png);
     }
+    #[test]
+    fn test_from_reader() {
+        let bytes: Vec<u8> = testing_png().as_bytes();
+        let png: Png = Png::from_reader(bytes.as_slice(), ParseMode::Strict).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+    }
+    #[test]
+    fn test_png_json() {
+        let png: Png = Png::from_chunks(testing_chunks());
+        let json: serde_json::Value = serde_json::to_value(&png).unwrap();
+        let chunks: &Vec<serde_json::Value> = json["chunks"].as_array().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0]["offset"], 8);
+        assert_eq!(chunks[1]["offset"], 8 + 12 + chunks[0]["length"].as_u64().unwrap());
+        assert_eq!(chunks[0]["crc_valid"], true);
+        assert_eq!(chunks[0]["type"]["name"], "FrSt");
+        assert_eq!(chunks[0]["type"]["critical"], true);
+    }
 
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
@@ -0,0 +1,152 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+/// What the specification says about a chunk type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub critical: bool,
+    // Whether the chunk may appear more than once
+    pub multiple: bool,
+    // Chunk types this chunk must come before, when present
+    pub before: &'static [&'static str],
+    // Chunk types this chunk must come after, when present
+    pub after: &'static [&'static str],
+}
+
+// Chunk types ancillary chunks must come before, either PLTE and IDAT or IDAT only
+const BEFORE_PALETTE: &[&str] = &["PLTE", "IDAT"];
+const BEFORE_DATA: &[&str] = &["IDAT"];
+
+// Chunk types defined by the PNG specification, its APNG extension and the registered
+// extensions, see http://www.libpng.org/pub/png/spec/register/pngext-1.4.0-pdg.html
+pub const REGISTERED_CHUNKS: &[ChunkInfo] = &[
+    // Critical chunks
+    info("IHDR", "Image header", true, false, &["PLTE", "IDAT", "IEND"], &[]),
+    info("PLTE", "Palette", true, false, BEFORE_DATA, &[]),
+    info("IDAT", "Image data", true, true, &["IEND"], &[]),
+    info("IEND", "Image trailer", true, false, &[], &["IHDR", "PLTE", "IDAT"]),
+    // Ancillary chunks of the specification
+    info("cHRM", "Primary chromaticities and white point", false, false, BEFORE_PALETTE, &[]),
+    info("gAMA", "Image gamma", false, false, BEFORE_PALETTE, &[]),
+    info("iCCP", "Embedded ICC profile", false, false, BEFORE_PALETTE, &[]),
+    info("sBIT", "Significant bits", false, false, BEFORE_PALETTE, &[]),
+    info("sRGB", "Standard RGB colour space", false, false, BEFORE_PALETTE, &[]),
+    info("cICP", "Coding-independent code points", false, false, BEFORE_PALETTE, &[]),
+    info("mDCV", "Mastering display colour volume", false, false, BEFORE_PALETTE, &[]),
+    info("cLLI", "Content light level information", false, false, BEFORE_PALETTE, &[]),
+    info("bKGD", "Background colour", false, false, BEFORE_DATA, &["PLTE"]),
+    info("hIST", "Image histogram", false, false, BEFORE_DATA, &["PLTE"]),
+    info("tRNS", "Transparency", false, false, BEFORE_DATA, &["PLTE"]),
+    info("eXIf", "Exchangeable image file profile", false, false, BEFORE_DATA, &[]),
+    info("pHYs", "Physical pixel dimensions", false, false, BEFORE_DATA, &[]),
+    info("sPLT", "Suggested palette", false, true, BEFORE_DATA, &[]),
+    info("tIME", "Image last-modification time", false, false, &[], &[]),
+    info("iTXt", "International textual data", false, true, &[], &[]),
+    info("tEXt", "Textual data", false, true, &[], &[]),
+    info("zTXt", "Compressed textual data", false, true, &[], &[]),
+    // Animated PNG
+    info("acTL", "Animation control", false, false, BEFORE_DATA, &[]),
+    info("fcTL", "Frame control", false, true, &[], &[]),
+    info("fdAT", "Frame data", false, true, &[], &["IDAT"]),
+    // Registered extensions
+    info("oFFs", "Image offset", false, false, BEFORE_DATA, &[]),
+    info("pCAL", "Calibration of pixel values", false, false, BEFORE_DATA, &[]),
+    info("sCAL", "Physical scale of image subject", false, false, BEFORE_DATA, &[]),
+    info("gIFg", "GIF graphic control extension", false, true, &[], &[]),
+    info("gIFx", "GIF application extension", false, true, &[], &[]),
+    info("gIFt", "GIF plain text extension (deprecated)", false, true, &[], &[]),
+    info("sTER", "Indicator of stereo image", false, false, BEFORE_DATA, &[]),
+    info("fRAc", "Fractal image parameters", false, true, &[], &[]),
+    info("dSIG", "Digital signature", false, true, &[], &[]),
+];
+
+const fn info(
+    name: &'static str, description: &'static str, critical: bool, multiple: bool,
+    before: &'static [&'static str], after: &'static [&'static str],
+) -> ChunkInfo {
+    ChunkInfo{name, description, critical, multiple, before, after}
+}
+
+// Returns what the specification says about a chunk type, if it is registered
+pub fn lookup(chunk_type: &ChunkType) -> Option<&'static ChunkInfo> {
+    REGISTERED_CHUNKS.iter().find(|info: &&ChunkInfo| info.name.as_bytes() == chunk_type.bytes())
+}
+
+// Whether a chunk type is defined by the specification or a registered extension
+pub fn is_registered(chunk_type: &ChunkType) -> bool {
+    lookup(chunk_type).is_some()
+}
+
+// Lists the registered ancillary chunks appearing more often than allowed, or out of the order
+// required by the specification. Critical chunks are left to `repair::check`.
+pub fn check_rules(chunks: &[Chunk]) -> Vec<String> {
+    let positions = |name: &str| -> Vec<usize> {
+        chunks.iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().bytes() == name.as_bytes())
+            .map(|(index, _)| index)
+            .collect()
+    };
+    let mut problems: Vec<String> = vec![];
+    for info in REGISTERED_CHUNKS.iter().filter(|info: &&ChunkInfo| !info.critical) {
+        let indexes: Vec<usize> = positions(info.name);
+        let (Some(first), Some(last)) = (indexes.first(), indexes.last()) else {
+            continue;
+        };
+        if !info.multiple && indexes.len() > 1 {
+            problems.push(format!("{} {} chunks instead of at most one", indexes.len(), info.name));
+        }
+        for other in info.before {
+            if positions(other).first().is_some_and(|index: &usize| index < last) {
+                problems.push(format!("{} chunk must come before {}", info.name, other));
+            }
+        }
+        for other in info.after {
+            if positions(other).last().is_some_and(|index: &usize| index > first) {
+                problems.push(format!("{} chunk must come after {}", info.name, other));
+            }
+        }
+    }
+    problems
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::registry;
+
+    #[test]
+    fn test_is_registered() {
+        assert!(registry::is_registered(&ChunkType::from_str("tEXt").unwrap()));
+        assert!(registry::is_registered(&ChunkType::from_str("fcTL").unwrap()));
+        assert!(!registry::is_registered(&ChunkType::from_str("ruSt").unwrap()));
+        assert!(!registry::is_registered(&ChunkType::from_str("TEXT").unwrap()));
+        assert_eq!(registry::lookup(&ChunkType::from_str("gAMA").unwrap()).unwrap().description, "Image gamma");
+        assert!(registry::lookup(&ChunkType::from_str("IDAT").unwrap()).unwrap().critical);
+    }
+    #[test]
+    fn test_check_rules() {
+        let chunk = |chunk_type: &[u8; 4]| Chunk::new(*chunk_type, vec![]);
+        let valid: Vec<Chunk> = ["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "tEXt", "IEND"]
+            .iter()
+            .map(|name: &&str| chunk(name.as_bytes().try_into().unwrap()))
+            .collect();
+        assert!(registry::check_rules(&valid).is_empty());
+
+        let invalid: Vec<Chunk> = vec![
+            chunk(b"IHDR"), chunk(b"tRNS"), chunk(b"PLTE"), chunk(b"IDAT"), chunk(b"gAMA"), chunk(b"gAMA"), chunk(b"IEND"),
+        ];
+        assert_eq!(registry::check_rules(&invalid), vec![
+            "2 gAMA chunks instead of at most one",
+            "gAMA chunk must come before PLTE",
+            "gAMA chunk must come before IDAT",
+            "tRNS chunk must come after PLTE",
+        ]);
+    }
+}
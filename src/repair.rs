@@ -0,0 +1,313 @@
+use std::error::Error;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+use crate::registry::{self, ChunkInfo};
+
+// Lists the structural problems of a PNG: damaged chunks, misplaced or missing critical chunks,
+// fragmented IDAT runs, ancillary chunks breaking the rules of the registry, broken APNG
+// sequences and trailing data.
+// An empty list means the structure is valid.
+pub fn check(png: &Png) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    if png.header() != &Png::STANDARD_HEADER {
+        problems.push(String::from("header is not standard"));
+    }
+
+    let chunks: Vec<Chunk> = png.chunks();
+    for (index, chunk) in chunks.iter().enumerate() {
+        if !chunk.chunk_type().is_alphabetic() {
+            problems.push(format!("chunk {} has an invalid chunk type", index));
+        } else if !chunk.is_crc_valid() {
+            problems.push(format!("chunk {} ({}) has a CRC mismatch", index, chunk.chunk_type()));
+        }
+    }
+
+    let positions = |chunk_type: &[u8; 4]| -> Vec<usize> {
+        chunks.iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().bytes() == *chunk_type)
+            .map(|(index, _)| index)
+            .collect()
+    };
+    let headers: Vec<usize> = positions(b"IHDR");
+    let palettes: Vec<usize> = positions(b"PLTE");
+    let data: Vec<usize> = positions(b"IDAT");
+    let ends: Vec<usize> = positions(b"IEND");
+
+    match headers.as_slice() {
+        [] => problems.push(String::from("IHDR chunk is missing")),
+        [0] => {}
+        [_] => problems.push(String::from("IHDR chunk is not the first chunk")),
+        _ => problems.push(format!("{} IHDR chunks instead of one", headers.len())),
+    }
+    if palettes.len() > 1 {
+        problems.push(format!("{} PLTE chunks instead of at most one", palettes.len()));
+    }
+    if let (Some(palette), Some(first_data)) = (palettes.first(), data.first()) {
+        if palette > first_data {
+            problems.push(String::from("PLTE chunk is after the first IDAT chunk"));
+        }
+    }
+    if data.is_empty() {
+        problems.push(String::from("IDAT chunk is missing"));
+    } else if data.last().unwrap() - data.first().unwrap() + 1 != data.len() {
+        problems.push(String::from("IDAT chunks are not consecutive"));
+    }
+    match ends.as_slice() {
+        [] => problems.push(String::from("IEND chunk is missing")),
+        [end] if *end == chunks.len() - 1 => {}
+        [_] => problems.push(String::from("IEND chunk is not the last chunk")),
+        _ => problems.push(format!("{} IEND chunks instead of one", ends.len())),
+    }
+    problems.extend(registry::check_rules(&chunks));
+    problems.extend(png.check_sequence());
+
+    if !png.trailing_data().is_empty() {
+        problems.push(format!("{} bytes of trailing data", png.trailing_data().len()));
+    }
+    problems
+}
+
+// Rebuilds a valid PNG out of a possibly damaged one, returning it along with the list of
+// every fix applied. Problems which cannot be fixed are still reported by `check`. Fails if the image cannot be recovered at all, without any IHDR chunk.
+pub fn repair(png: &Png) -> Result<(Png, Vec<String>), Box<dyn Error>> {
+    let mut fixes: Vec<String> = vec![];
+    if png.header() != &Png::STANDARD_HEADER {
+        fixes.push(String::from("restored the standard header"));
+    }
+
+    // Drop unreadable chunks and recompute the CRC of the others
+    let mut chunks: Vec<Chunk> = vec![];
+    for (index, chunk) in png.chunks().into_iter().enumerate() {
+        if !chunk.chunk_type().is_alphabetic() {
+            fixes.push(format!("dropped chunk {} with an invalid chunk type", index));
+        } else if !chunk.is_crc_valid() {
+            fixes.push(format!("recomputed the CRC of chunk {} ({})", index, chunk.chunk_type()));
+            chunks.push(Chunk::new(chunk.chunk_type().bytes(), chunk.data().to_vec()));
+        } else {
+            chunks.push(chunk);
+        }
+    }
+
+    // Sort chunks around the IDAT run, keeping their relative order otherwise
+    let mut header: Option<Chunk> = None;
+    let mut has_palette: bool = false;
+    let mut has_end: bool = false;
+    let mut before_data: Vec<Chunk> = vec![];
+    let mut data: Vec<Chunk> = vec![];
+    let mut after_data: Vec<Chunk> = vec![];
+    let mut since_last_data: usize = 0;
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        match &chunk.chunk_type().bytes() {
+            b"IHDR" => {
+                if header.is_some() {
+                    fixes.push(format!("dropped duplicate IHDR chunk {}", index));
+                } else {
+                    if index != 0 {
+                        fixes.push(String::from("moved the IHDR chunk to the front"));
+                    }
+                    header = Some(chunk);
+                }
+            }
+            b"PLTE" => {
+                if has_palette {
+                    fixes.push(format!("dropped duplicate PLTE chunk {}", index));
+                } else {
+                    if !data.is_empty() {
+                        fixes.push(String::from("moved the PLTE chunk before the IDAT chunks"));
+                    }
+                    has_palette = true;
+                    before_data.push(chunk);
+                }
+            }
+            b"IDAT" => {
+                if !data.is_empty() && since_last_data != 0 {
+                    fixes.push(format!("moved {} chunks out of the IDAT run", since_last_data));
+                }
+                since_last_data = 0;
+                data.push(chunk);
+            }
+            b"IEND" => {
+                if has_end {
+                    fixes.push(format!("dropped duplicate IEND chunk {}", index));
+                }
+                has_end = true;
+            }
+            _ => {
+                if data.is_empty() {
+                    before_data.push(chunk);
+                } else {
+                    since_last_data += 1;
+                    after_data.push(chunk);
+                }
+            }
+        }
+    }
+
+    let header: Chunk = header.ok_or("No IHDR chunk found, the image cannot be repaired")?;
+    if !has_end {
+        fixes.push(String::from("added the missing IEND chunk"));
+    }
+    if !png.trailing_data().is_empty() {
+        fixes.push(format!("dropped {} bytes of trailing data", png.trailing_data().len()));
+    }
+
+    let repaired: Vec<Chunk> = [header]
+        .into_iter()
+        .chain(before_data)
+        .chain(data)
+        .chain(after_data)
+        .chain([Chunk::new(*b"IEND", vec![])])
+        .collect();
+    let repaired: Vec<Chunk> = fix_rules(repaired, &mut fixes);
+    Ok((Png::from_chunks(repaired), fixes))
+}
+
+// Drops the duplicates of the registered ancillary chunks allowed only once, and moves those
+// which must come before PLTE or IDAT, or after PLTE, to the closest valid position. Other
+// rules, such as fdAT chunks coming before IDAT, cannot be fixed without breaking the
+// animation and are left to `check`.
+fn fix_rules(chunks: Vec<Chunk>, fixes: &mut Vec<String>) -> Vec<Chunk> {
+    let palette: Option<usize> = chunks.iter().position(|chunk: &Chunk| chunk.chunk_type().bytes() == *b"PLTE");
+    let first_data: Option<usize> = chunks.iter().position(|chunk: &Chunk| chunk.chunk_type().bytes() == *b"IDAT");
+    let mut seen: Vec<&str> = vec![];
+    let mut kept: Vec<Chunk> = vec![];
+    let mut before_palette: Vec<Chunk> = vec![];
+    let mut after_palette: Vec<Chunk> = vec![];
+    let mut before_data: Vec<Chunk> = vec![];
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let Some(info) = registry::lookup(chunk.chunk_type()).filter(|info: &&ChunkInfo| !info.critical) else {
+            kept.push(chunk);
+            continue;
+        };
+        if !info.multiple && seen.contains(&info.name) {
+            fixes.push(format!("dropped duplicate {} chunk {}", info.name, index));
+            continue;
+        }
+        seen.push(info.name);
+        let must_precede = |name: &str, position: Option<usize>| -> bool {
+            info.before.contains(&name) && position.is_some_and(|position: usize| position < index)
+        };
+        if must_precede("PLTE", palette) {
+            fixes.push(format!("moved the {} chunk before the PLTE chunk", info.name));
+            before_palette.push(chunk);
+        } else if must_precede("IDAT", first_data) {
+            fixes.push(format!("moved the {} chunk before the IDAT chunks", info.name));
+            before_data.push(chunk);
+        } else if info.after.contains(&"PLTE") && palette.is_some_and(|palette: usize| palette > index) {
+            fixes.push(format!("moved the {} chunk after the PLTE chunk", info.name));
+            after_palette.push(chunk);
+        } else {
+            kept.push(chunk);
+        }
+    }
+
+    // Chunks moved before the data go before the fcTL chunk of the default image, if any
+    let mut insertion: usize = kept.iter()
+        .position(|chunk: &Chunk| chunk.chunk_type().bytes() == *b"IDAT")
+        .unwrap_or(kept.len());
+    if insertion > 0 && kept[insertion - 1].chunk_type().bytes() == *b"fcTL" {
+        insertion -= 1;
+    }
+    let mut result: Vec<Chunk> = vec![];
+    for (index, chunk) in kept.into_iter().enumerate() {
+        if index == insertion {
+            result.append(&mut before_data);
+        }
+        if chunk.chunk_type().bytes() == *b"PLTE" {
+            result.append(&mut before_palette);
+            result.push(chunk);
+            result.append(&mut after_palette);
+        } else {
+            result.push(chunk);
+        }
+    }
+    result.append(&mut before_data);
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::png::{ParseMode, Png};
+    use crate::registry;
+    use crate::repair;
+
+    fn chunk(chunk_type: &[u8; 4], data: &str) -> Chunk {
+        Chunk::new(*chunk_type, data.as_bytes().to_vec())
+    }
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk: &Chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_valid_structure() {
+        let png: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"IDAT", "data"), chunk(b"IEND", ""),
+        ]);
+        assert!(repair::check(&png).is_empty());
+
+        let (repaired, fixes) = repair::repair(&png).unwrap();
+        assert!(fixes.is_empty());
+        assert_eq!(repaired.as_bytes(), png.as_bytes());
+    }
+    #[test]
+    fn test_repair_structure() {
+        let png: Png = Png::from_chunks(vec![
+            chunk(b"ruSt", "message"),
+            chunk(b"IHDR", "header"),
+            chunk(b"IDAT", "first"),
+            chunk(b"tEXt", "text"),
+            chunk(b"PLTE", "palette"),
+            chunk(b"IDAT", "second"),
+        ]);
+        assert_eq!(repair::check(&png).len(), 4);
+
+        let (repaired, fixes) = repair::repair(&png).unwrap();
+        assert_eq!(chunk_types(&repaired), vec!["IHDR", "ruSt", "PLTE", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(fixes.len(), 4);
+        assert!(repair::check(&repaired).is_empty());
+    }
+    #[test]
+    fn test_repair_chunk_order() {
+        let png: Png = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"),
+            chunk(b"tRNS", "transparency"),
+            chunk(b"PLTE", "palette"),
+            chunk(b"gAMA", "gamma"),
+            chunk(b"fcTL", "frame"),
+            chunk(b"IDAT", "data"),
+            chunk(b"pHYs", "dimensions"),
+            chunk(b"tIME", "time"),
+            chunk(b"tIME", "time"),
+            chunk(b"IEND", ""),
+        ]);
+        let (repaired, fixes) = repair::repair(&png).unwrap();
+        assert_eq!(chunk_types(&repaired), vec!["IHDR", "gAMA", "PLTE", "tRNS", "pHYs", "fcTL", "IDAT", "tIME", "IEND"]);
+        assert_eq!(fixes.len(), 4);
+        assert!(registry::check_rules(&repaired.chunks()).is_empty());
+    }
+    #[test]
+    fn test_repair_damaged_chunks() {
+        let mut bytes: Vec<u8> = Png::from_chunks(vec![
+            chunk(b"IHDR", "header"), chunk(b"IDAT", "data"), chunk(b"IEND", ""),
+        ]).as_bytes();
+        // Alter the IDAT data, then append trailing garbage
+        bytes[8 + 18 + 8] = b'D';
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let png: Png = Png::parse(&bytes, ParseMode::Lenient).unwrap();
+        let (repaired, fixes) = repair::repair(&png).unwrap();
+        assert_eq!(fixes.len(), 2);
+        assert!(!repaired.is_damaged());
+        assert_eq!(repaired.chunk_by_type("IDAT").unwrap().data(), "Data".as_bytes());
+        assert!(repaired.trailing_data().is_empty());
+    }
+    #[test]
+    fn test_repair_without_header() {
+        let png: Png = Png::from_chunks(vec![chunk(b"IDAT", "data"), chunk(b"IEND", "")]);
+        assert!(repair::repair(&png).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::analysis::ChannelAnalysis;
+use crate::batch::FileResult;
+use crate::carrier::Carrier;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::diff::{self, ChunkChange, PixelDiff};
+use crate::envelope::Envelope;
+use crate::png::Png;
+use crate::repair;
+use crate::scan::Finding;
+use crate::strip::Stripped;
+
+/// How results are printed on stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+// Whether a path given on the command line stands for stdin or stdout
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+// Writes a value as pretty JSON
+pub fn json<T: Serialize>(out: &mut dyn Write, value: &T) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+// Reports where a payload has been hidden, along with the chunk type generated for it if any
+pub fn encoded(
+    out: &mut dyn Write, format: OutputFormat, output_filename: &Path, method: &str, chunk_type: Option<&str>,
+    generated: bool
+) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"output": output_filename, "method": method, "chunk_type": chunk_type}));
+    }
+    if let (Some(chunk_type), true) = (chunk_type, generated) {
+        writeln!(out, "Generated chunk type: {}", chunk_type)?;
+    }
+    if !is_stdio(output_filename) {
+        writeln!(out, "Payload hidden in {}", output_filename.display())?;
+    }
+    Ok(())
+}
+
+// Reports the envelope found by decode, printing its payload unless it has been written to
+// `output_filename`. Repaired bytes are reported on stderr.
+pub fn decoded(
+    out: &mut dyn Write, format: OutputFormat, envelope: Option<&Envelope>, output_filename: Option<&Path>
+) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"envelope": envelope, "output": envelope.and(output_filename)}));
+    }
+    let Some(envelope) = envelope else {
+        writeln!(out, "No message for you here!")?;
+        return Ok(());
+    };
+    if envelope.corrected_bytes() != 0 {
+        eprintln!("{} corrupted bytes have been repaired", envelope.corrected_bytes());
+    }
+    match output_filename {
+        Some(output_filename) => writeln!(
+            out, "{} bytes ({}) written to {}",
+            envelope.payload().len(), envelope.mime_type(), output_filename.display()
+        )?,
+        None => {
+            writeln!(out, "The following message has been found for you: {}", envelope)?;
+            writeln!(out, "{}", String::from_utf8_lossy(envelope.payload()))?;
+        }
+    }
+    Ok(())
+}
+
+// Reports how many payloads a method removed
+pub fn removed(out: &mut dyn Write, format: OutputFormat, method: &str, removed: usize) -> Result<(), Box<dyn Error>> {
+    match (format, removed) {
+        (OutputFormat::Json, _) => json(out, &json!({"method": method, "removed": removed}))?,
+        (OutputFormat::Text, 0) => writeln!(out, "Nothing to remove with the {} method", method)?,
+        (OutputFormat::Text, _) => writeln!(out, "Removed {} payloads with the {} method", removed, method)?,
+    }
+    Ok(())
+}
+
+// Warnings about the unregistered chunks dropped by a method rewriting the image data, as
+// they aren't safe to copy once it changes
+pub fn unsafe_to_copy_warnings(method: &dyn Carrier, png: &Png) -> Vec<String> {
+    if !method.rewrites_image_data() {
+        return vec![];
+    }
+    png.clone()
+        .strip_unsafe_to_copy()
+        .iter()
+        .map(|chunk: &Chunk| format!(
+            "dropping the {} chunk, which isn't safe to copy once the {} method rewrites the image data",
+            chunk.chunk_type(), method.name()
+        ))
+        .collect()
+}
+
+// Prints a PNG in full
+pub fn png(out: &mut dyn Write, format: OutputFormat, png: &Png) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Text => writeln!(out, "{}", png)?,
+        OutputFormat::Json => json(out, png)?,
+    }
+    Ok(())
+}
+
+// Prints one line per chunk. Flags are C (critical), P (public), R (reserved bit valid)
+// and S (safe to copy), a dash standing for the opposite property.
+pub fn chunk_table(out: &mut dyn Write, format: OutputFormat, png: &Png) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, png);
+    }
+    writeln!(out, "{:>5}  {:>10}  {:<4}  {:<5}  {:>10}  {:<8}  Summary", "Index", "Offset", "Type", "Flags", "Length", "CRC")?;
+    for (index, (offset, chunk)) in png.chunk_offsets().into_iter().zip(png.chunks()).enumerate() {
+        let chunk_type: &ChunkType = chunk.chunk_type();
+        let flags: String = [
+            (chunk_type.is_critical(), 'C'),
+            (chunk_type.is_public(), 'P'),
+            (chunk_type.is_reserved_bit_valid(), 'R'),
+            (chunk_type.is_safe_to_copy(), 'S'),
+        ].iter().map(|(set, flag)| if *set { *flag } else { '-' }).collect();
+        writeln!(
+            out, "{:>5}  {:>10}  {:<4}  {:<5}  {:>10}  {:<8}  {}",
+            index, offset, chunk_type, flags, chunk.length(),
+            if chunk.is_crc_valid() { "ok" } else { "mismatch" },
+            chunk.summary().or(chunk.description().map(String::from)).unwrap_or_default()
+        )?;
+    }
+    if !png.trailing_data().is_empty() {
+        writeln!(out, "{} bytes of trailing data", png.trailing_data().len())?;
+    }
+    Ok(())
+}
+
+// Prints the data of a chunk as a hexdump
+pub fn dump(out: &mut dyn Write, format: OutputFormat, index: usize, chunk: &Chunk) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"index": index, "chunk": chunk, "data": BASE64.encode(chunk.data())}));
+    }
+    writeln!(out, "Chunk {} ({}, {} bytes):", index, chunk.chunk_type(), chunk.length())?;
+    write!(out, "{}", hexdump(chunk.data()))?;
+    Ok(())
+}
+
+// Formats bytes as lines of 16 hexadecimal bytes, preceded by their offset and followed by
+// their printable ASCII characters
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut dump: String = String::new();
+    for (line, row) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = (0..16)
+            .map(|i: usize| row.get(i).map_or(String::from("  "), |byte: &u8| format!("{:02x}", byte)))
+            .collect();
+        let ascii: String = row
+            .iter()
+            .map(|byte: &u8| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+        dump.push_str(&format!("{:08x}  {}  {}  |{}|\n", line * 16, hex[..8].join(" "), hex[8..].join(" "), ascii));
+    }
+    dump
+}
+
+// Reports the bytes of a chunk written to a file by extract-chunk
+pub fn extracted(
+    out: &mut dyn Write, format: OutputFormat, index: usize, chunk: &Chunk, output_filename: &Path, length: usize
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Text => writeln!(
+            out, "{} bytes of chunk {} ({}) written to {}",
+            length, index, chunk.chunk_type(), output_filename.display()
+        )?,
+        OutputFormat::Json => json(out, &json!({
+            "index": index,
+            "chunk": chunk,
+            "output": output_filename,
+            "bytes": length,
+        }))?,
+    }
+    Ok(())
+}
+
+// Reports the chunk added by insert-chunk
+pub fn inserted(
+    out: &mut dyn Write, format: OutputFormat, index: usize, chunk: &Chunk, output_filename: &Path
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Text => writeln!(out, "Inserted {} chunk at index {}", chunk.chunk_type(), index)?,
+        OutputFormat::Json => json(out, &json!({"index": index, "chunk": chunk, "output": output_filename}))?,
+    }
+    Ok(())
+}
+
+// Prints the findings of a scan, one per line
+pub fn findings(out: &mut dyn Write, format: OutputFormat, findings: &[Finding]) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"findings": findings}));
+    }
+    for finding in findings {
+        writeln!(out, "{}", finding)?;
+    }
+    Ok(())
+}
+
+// Prints the embedding rates estimated for each channel as a table
+pub fn analysis(out: &mut dyn Write, format: OutputFormat, analyses: &[ChannelAnalysis]) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        let channels: Vec<serde_json::Value> = analyses
+            .iter()
+            .map(|channel: &ChannelAnalysis| {
+                let mut value: serde_json::Value = json!(channel);
+                value["estimated_rate"] = json!(channel.estimated_rate());
+                value
+            })
+            .collect();
+        return json(out, &json!({"channels": channels}));
+    }
+    writeln!(out, "{:<8} {:>10} {:>10} {:>8} {:>8} {:>10}", "Channel", "Chi² p", "Chi² rate", "RS", "SPA", "Estimate")?;
+    for channel in analyses.iter() {
+        writeln!(
+            out, "{:<8} {:>10.4} {:>9.1}% {:>7.1}% {:>7.1}% {:>9.1}%",
+            channel.channel, channel.chi_square_p_value, channel.chi_square_rate * 100.0,
+            channel.rs_rate * 100.0, channel.spa_rate * 100.0, channel.estimated_rate() * 100.0
+        )?;
+    }
+    Ok(())
+}
+
+// Reports what strip removed
+pub fn stripped(
+    out: &mut dyn Write, format: OutputFormat, stripped: &Stripped, output_filename: &Path
+) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"removed": stripped, "output": output_filename}));
+    }
+    if stripped.is_empty() {
+        writeln!(out, "Nothing to strip")?;
+        return Ok(());
+    }
+    for chunk in stripped.chunks.iter() {
+        writeln!(out, "Removed {} chunk ({} bytes)", chunk.chunk_type(), chunk.length())?;
+    }
+    if stripped.trailing_data_length != 0 {
+        writeln!(out, "Removed {} bytes of trailing data", stripped.trailing_data_length)?;
+    }
+    Ok(())
+}
+
+// Prints the differences between two PNGs: chunk changes, trailing data and optionally pixels
+pub fn diff(
+    out: &mut dyn Write, format: OutputFormat, before: &Png, after: &Png, pixels: Option<&PixelDiff>
+) -> Result<(), Box<dyn Error>> {
+    let changes: Vec<ChunkChange> = diff::chunk_changes(before, after);
+    let trailing_data: (usize, usize) = (before.trailing_data().len(), after.trailing_data().len());
+    let trailing_data_changed: bool = before.trailing_data() != after.trailing_data();
+    if format == OutputFormat::Json {
+        return json(out, &json!({
+            "changes": changes,
+            "trailing_data": {"before": trailing_data.0, "after": trailing_data.1, "changed": trailing_data_changed},
+            "pixels": pixels,
+        }));
+    }
+    if changes.is_empty() {
+        writeln!(out, "No chunk changed")?;
+    }
+    for change in changes.iter() {
+        writeln!(out, "{}", change)?;
+    }
+    if trailing_data.0 != trailing_data.1 {
+        writeln!(out, "Trailing data: {} -> {} bytes", trailing_data.0, trailing_data.1)?;
+    } else if trailing_data_changed {
+        writeln!(out, "Trailing data changed ({} bytes)", trailing_data.1)?;
+    }
+    if let Some(pixels) = pixels {
+        writeln!(out, "Pixels: {}", pixels)?;
+    }
+    Ok(())
+}
+
+// Reports the fixes applied by repair, and the problems left
+pub fn repaired(out: &mut dyn Write, format: OutputFormat, fixes: &[String], problems: &[String]) -> Result<(), Box<dyn Error>> {
+    if format == OutputFormat::Json {
+        return json(out, &json!({"fixes": fixes, "unrepaired": problems}));
+    }
+    if fixes.is_empty() && problems.is_empty() {
+        writeln!(out, "Nothing to repair")?;
+        return Ok(());
+    }
+    for fix in fixes.iter() {
+        writeln!(out, "Repaired: {}", fix)?;
+    }
+    for problem in problems.iter() {
+        writeln!(out, "Could not repair: {}", problem)?;
+    }
+    Ok(())
+}
+
+// Reports what a mutating command would change without writing anything: chunks added and
+// removed, the size delta and the structural check of the result
+pub fn dry_run(
+    out: &mut dyn Write, format: OutputFormat, output_filename: &Path, before: &Png, after: &Png
+) -> Result<(), Box<dyn Error>> {
+    let changes: Vec<ChunkChange> = diff::chunk_changes(before, after);
+    let (size_before, size_after) = (before.as_bytes().len(), after.as_bytes().len());
+    let problems: Vec<String> = repair::check(after);
+
+    if format == OutputFormat::Json {
+        return json(out, &json!({
+            "dry_run": true,
+            "output": output_filename,
+            "changes": changes,
+            "size_before": size_before,
+            "size_after": size_after,
+            "size_delta": size_after as i64 - size_before as i64,
+            "problems": problems,
+        }));
+    }
+    writeln!(out, "Dry run, nothing written to {}", output_filename.display())?;
+    if changes.is_empty() {
+        writeln!(out, "No chunk changed")?;
+    }
+    for change in changes.iter() {
+        writeln!(out, "{}", change)?;
+    }
+    writeln!(out, "Size: {} -> {} bytes ({:+})", size_before, size_after, size_after as i64 - size_before as i64)?;
+    if problems.is_empty() {
+        writeln!(out, "Structure: valid")?;
+    }
+    for problem in problems {
+        writeln!(out, "Structure: {}", problem)?;
+    }
+    Ok(())
+}
+
+// Prints the results of a command run on several files on stdout, followed by a summary.
+// Fails if the command failed on any file.
+pub fn batch(format: OutputFormat, results: &[FileResult]) -> Result<(), Box<dyn Error>> {
+    let failed: usize = results.iter().filter(|result: &&FileResult| result.error.is_some()).count();
+    let mut stdout: io::StdoutLock = io::stdout().lock();
+    if format == OutputFormat::Json {
+        let files: Vec<serde_json::Value> = results
+            .iter()
+            .map(|result: &FileResult| json!({
+                "path": result.path,
+                "result": serde_json::from_slice::<serde_json::Value>(&result.output).ok(),
+                "error": result.error,
+            }))
+            .collect();
+        json(&mut stdout, &json!({
+            "files": files,
+            "succeeded": results.len() - failed,
+            "failed": failed,
+        }))?;
+    } else {
+        // Files the command had nothing to say about are left out, e.g. clean files for scan
+        for result in results.iter().filter(|result: &&FileResult| !result.output.is_empty() || result.error.is_some()) {
+            writeln!(stdout, "==> {} <==", result.path.display())?;
+            stdout.write_all(&result.output)?;
+            if let Some(error) = &result.error {
+                writeln!(stdout, "Error: {}", error)?;
+            }
+        }
+        writeln!(stdout, "{} files processed: {} succeeded, {} failed", results.len(), results.len() - failed, failed)?;
+    }
+    if failed != 0 {
+        return Err(format!("{} of {} files failed", failed, results.len()).into());
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::report;
+
+    #[test]
+    fn test_hexdump() {
+        let dump: String = report::hexdump(b"Hello, world!\x00\x01\x02\x03");
+        assert_eq!(dump.lines().count(), 2);
+        assert!(dump.starts_with("00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 01 02  |Hello, world!...|"));
+        assert!(dump.lines().nth(1).unwrap().starts_with("00000010  03"));
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::carrier::CarrierRegistry;
+use crate::chunk::Chunk;
+use crate::envelope::Envelope;
+use crate::png::Png;
+use crate::registry;
+
+// Text chunks longer than this are reported by default, as real metadata rarely is
+pub const DEFAULT_MAX_TEXT_LENGTH: usize = 1024;
+
+/// Something in a PNG which may hide data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    // A chunk whose type is neither in the spec nor a registered extension
+    UnregisteredChunk { index: usize, chunk_type: String, length: u32 },
+    // Bytes after the IEND chunk
+    TrailingData { length: usize },
+    // An envelope written by this tool, `readable` telling whether its header says it opens
+    // without a passphrase
+    Envelope { location: String, readable: bool },
+    // A text chunk larger than the threshold
+    OversizedText { index: usize, chunk_type: String, length: u32 },
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UnregisteredChunk { index, chunk_type, length } => {
+                write!(f, "unregistered {} chunk at index {} ({} bytes)", chunk_type, index, length)
+            }
+            Finding::TrailingData { length } => write!(f, "{} bytes of trailing data after IEND", length),
+            Finding::Envelope { location, readable } => write!(
+                f, "message envelope in {}{}", location, if *readable { "" } else { " (encrypted or unsupported)" }
+            ),
+            Finding::OversizedText { index, chunk_type, length } => {
+                write!(f, "oversized {} chunk at index {} ({} bytes)", chunk_type, index, length)
+            }
+        }
+    }
+}
+
+// Lists everything in a PNG which may hide data: unregistered chunk types, trailing data,
+// envelopes found by any built-in carrier, and text chunks longer than `max_text_length`.
+// Carriers decoding the image data are only tried with `decode_pixels`, as they are slow.
+pub fn scan(png: &Png, max_text_length: usize, decode_pixels: bool) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = vec![];
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type: String = chunk.chunk_type().to_string();
+        if !registry::is_registered(chunk.chunk_type()) {
+            findings.push(Finding::UnregisteredChunk{index, chunk_type: chunk_type.clone(), length: chunk.length()});
+        }
+        if is_text_chunk(chunk) && chunk.length() as usize > max_text_length {
+            findings.push(Finding::OversizedText{index, chunk_type: chunk_type.clone(), length: chunk.length()});
+        }
+        if Envelope::is_envelope(chunk.data()) {
+            findings.push(envelope_finding(format!("{} chunk at index {}", chunk_type, index), chunk.data()));
+        }
+    }
+    if !png.trailing_data().is_empty() {
+        findings.push(Finding::TrailingData{length: png.trailing_data().len()});
+    }
+
+    // Every chunk has been checked above, leaving carriers which hide data elsewhere
+    for carrier in CarrierRegistry::default().carriers() {
+        if carrier.name() == "chunk" || (carrier.decodes_image_data() && !decode_pixels) {
+            continue;
+        }
+        for bytes in carrier.extract(png).unwrap_or_default() {
+            if Envelope::is_envelope(&bytes) {
+                findings.push(envelope_finding(format!("{} carrier", carrier.name()), &bytes));
+            }
+        }
+    }
+    findings
+}
+
+fn envelope_finding(location: String, bytes: &[u8]) -> Finding {
+    Finding::Envelope{location, readable: Envelope::is_readable(bytes)}
+}
+
+// Whether a chunk is one of the text chunks, which may hold arbitrary data
+pub fn is_text_chunk(chunk: &Chunk) -> bool {
+    matches!(&chunk.chunk_type().bytes(), b"tEXt" | b"zTXt" | b"iTXt")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use crate::carrier::{Carrier, LsbCarrier, Placement};
+    use crate::chunk::Chunk;
+    use crate::envelope::Envelope;
+    use crate::png::Png;
+    use crate::scan::{self, Finding};
+
+    #[test]
+    fn test_scan() {
+        let mut png: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0; 13]),
+            Chunk::new(*b"tEXt", [b"Comment\0".to_vec(), vec![b'a'; 100]].concat()),
+            Chunk::new(*b"ruSt", Envelope::from_message("Hidden").to_bytes().unwrap()),
+            Chunk::new(*b"IDAT", vec![0; 10]),
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+        png.set_trailing_data(vec![1, 2, 3]);
+
+        let findings: Vec<Finding> = scan::scan(&png, 64, false);
+        assert_eq!(findings, vec![
+            Finding::OversizedText{index: 1, chunk_type: String::from("tEXt"), length: 108},
+            Finding::UnregisteredChunk{index: 2, chunk_type: String::from("ruSt"), length: 51},
+            Finding::Envelope{location: String::from("ruSt chunk at index 2"), readable: true},
+            Finding::TrailingData{length: 3},
+        ]);
+        assert!(scan::scan(&Png::from_chunks(vec![Chunk::new(*b"IEND", vec![])]), 64, false).is_empty());
+    }
+    #[test]
+    fn test_scan_pixels() {
+        let filtered: Vec<u8> = (0..16).flat_map(|_| [0].into_iter().chain(0..64)).collect();
+        let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        let mut png: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0, 0, 0, 16, 0, 0, 0, 16, 8, 6, 0, 0, 0]),
+            Chunk::new(*b"IDAT", encoder.finish().unwrap()),
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+        let bytes: Vec<u8> = Envelope::from_message("Hidden").to_bytes().unwrap();
+        LsbCarrier.embed(&mut png, &bytes, Placement::Random).unwrap();
+
+        assert!(scan::scan(&png, 64, false).is_empty());
+        assert_eq!(scan::scan(&png, 64, true), vec![
+            Finding::Envelope{location: String::from("lsb carrier"), readable: true},
+        ]);
+    }
+}
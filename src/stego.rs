@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::carrier::{Carrier, CarrierRegistry, ChunkCarrier, Placement};
+use crate::compression::Compression;
+use crate::envelope::Envelope;
+use crate::png::Png;
+
+/// Hides payloads in a PNG, e.g.
+/// `Encoder::new(png).passphrase("secret").compress(Compression::Zstd).embed(payload)`
+#[derive(Clone)]
+pub struct Encoder {
+    png: Png,
+    method: Arc<dyn Carrier>,
+    placement: Placement,
+    passphrase: Option<String>,
+    compression: Option<Compression>,
+    parity: Option<u8>,
+}
+impl Encoder {
+    // Init function
+    pub fn new(png: Png) -> Encoder {
+        Encoder{
+            png,
+            method: Arc::new(ChunkCarrier::default()),
+            placement: Placement::default(),
+            passphrase: None,
+            compression: None,
+            parity: None,
+        }
+    }
+
+    // Sets the carrier hiding the payload, a chunk of the default type otherwise
+    pub fn method(mut self, method: Arc<dyn Carrier>) -> Encoder {
+        self.method = method;
+        self
+    }
+    // Sets where the chunk holding the payload is inserted, for carriers using chunks
+    pub fn placement(mut self, placement: Placement) -> Encoder {
+        self.placement = placement;
+        self
+    }
+    // Encrypts the payload with this passphrase
+    pub fn passphrase(mut self, passphrase: &str) -> Encoder {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+    // Compresses the payload, overriding the compression set on the envelope
+    pub fn compress(mut self, compression: Compression) -> Encoder {
+        self.compression = Some(compression);
+        self
+    }
+    // Adds Reed-Solomon codes to the payload, overriding the parity set on the envelope
+    pub fn error_correction(mut self, parity: u8) -> Encoder {
+        self.parity = Some(parity);
+        self
+    }
+
+    // Hides the payload, returning the resulting PNG
+    pub fn embed<P: Into<Envelope>>(self, payload: P) -> Result<Png, Box<dyn Error>> {
+        let mut envelope: Envelope = payload.into();
+        if let Some(compression) = self.compression {
+            envelope = envelope.with_compression(compression);
+        }
+        if let Some(parity) = self.parity {
+            envelope = envelope.with_error_correction(parity);
+        }
+        let bytes: Vec<u8> = envelope.seal(self.passphrase.as_deref())?;
+
+        let mut png: Png = self.png;
+        if let Some(capacity) = self.method.capacity(&png)? {
+            if bytes.len() > capacity {
+                return Err(format!(
+                    "Payload too large for the {} method ({} bytes, capacity of {} bytes)",
+                    self.method.name(), bytes.len(), capacity
+                ).into());
+            }
+        }
+        self.method.embed(&mut png, &bytes, self.placement)?;
+        Ok(png)
+    }
+}
+
+/// Reads back payloads hidden in a PNG, e.g. `Decoder::new(&png).passphrase("secret").extract_all()`
+#[derive(Clone)]
+pub struct Decoder<'a> {
+    png: &'a Png,
+    method: Option<Arc<dyn Carrier>>,
+    registry: CarrierRegistry,
+    passphrase: Option<String>,
+}
+impl<'a> Decoder<'a> {
+    // Init function
+    pub fn new(png: &'a Png) -> Decoder<'a> {
+        Decoder{
+            png,
+            method: None,
+            registry: CarrierRegistry::default(),
+            passphrase: None,
+        }
+    }
+
+    // Only looks for payloads hidden with this carrier. Without it, every carrier of the
+    // registry is searched for envelopes.
+    pub fn method(mut self, method: Arc<dyn Carrier>) -> Decoder<'a> {
+        self.method = Some(method);
+        self
+    }
+    // Sets the carriers searched when no method is given, the built-in ones otherwise
+    pub fn registry(mut self, registry: CarrierRegistry) -> Decoder<'a> {
+        self.registry = registry;
+        self
+    }
+    // Decrypts payloads with this passphrase
+    pub fn passphrase(mut self, passphrase: &str) -> Decoder<'a> {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+
+    // Returns the first payload found, if any
+    pub fn extract(&self) -> Result<Option<Envelope>, Box<dyn Error>> {
+        Ok(self.extract_all()?.into_iter().next())
+    }
+
+    // Returns every payload found, failing if one of them cannot be read
+    pub fn extract_all(&self) -> Result<Vec<Envelope>, Box<dyn Error>> {
+        let passphrase: Option<&str> = self.passphrase.as_deref();
+        let mut envelopes: Vec<Envelope> = vec![];
+        match &self.method {
+            Some(method) => {
+                for bytes in method.extract(self.png)? {
+                    if Envelope::is_envelope(&bytes) {
+                        envelopes.push(Envelope::open(&bytes, passphrase)?);
+                    } else if method.reads_bare_messages() {
+                        // Such payloads may predate envelopes, read them as bare messages
+                        envelopes.push(Envelope::from_chunk_data(&bytes, passphrase)?);
+                    }
+                }
+            }
+            None => {
+                for method in self.registry.carriers() {
+                    // Carriers which don't apply to this image, e.g. LSB on a palette image, hide nothing
+                    let Ok(extracted) = method.extract(self.png) else {
+                        continue;
+                    };
+                    for bytes in extracted.iter().filter(|bytes: &&Vec<u8>| Envelope::is_envelope(bytes)) {
+                        envelopes.push(
+                            Envelope::open(bytes, passphrase)
+                                .map_err(|err| format!("{} method: {}", method.name(), err))?
+                        );
+                    }
+                }
+            }
+        }
+        Ok(envelopes)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use crate::carrier::{ChunkCarrier, Placement, TrailerCarrier};
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::compression::Compression;
+    use crate::envelope::Envelope;
+    use crate::png::Png;
+    use crate::stego::{Decoder, Encoder};
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0; 13]),
+            Chunk::new(*b"IDAT", vec![0; 10]),
+            Chunk::new(*b"IEND", vec![]),
+        ])
+    }
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk: &Chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let png: Png = Encoder::new(testing_png())
+            .compress(Compression::Deflate)
+            .error_correction(8)
+            .embed(String::from("Hello"))
+            .unwrap();
+        let png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+
+        let envelope: Envelope = Decoder::new(&png).extract().unwrap().unwrap();
+        assert_eq!(envelope.payload(), "Hello".as_bytes());
+        assert_eq!(envelope.parity(), 8);
+    }
+    #[test]
+    fn test_placement() {
+        let png: Png = Encoder::new(testing_png())
+            .placement(Placement::AfterHeader)
+            .embed(String::from("Hello"))
+            .unwrap();
+        assert_eq!(chunk_types(&png), vec!["IHDR", "ruSt", "IDAT", "IEND"]);
+
+        let png: Png = Encoder::new(testing_png())
+            .method(Arc::new(ChunkCarrier::new(ChunkType::from_str("abCd").unwrap())))
+            .placement(Placement::BeforeEnd)
+            .embed(String::from("Hello"))
+            .unwrap();
+        assert_eq!(chunk_types(&png), vec!["IHDR", "IDAT", "abCd", "IEND"]);
+    }
+    #[test]
+    fn test_passphrase() {
+        let png: Png = Encoder::new(testing_png())
+            .passphrase("secret")
+            .embed(vec![1, 2, 3])
+            .unwrap();
+
+        assert!(Decoder::new(&png).extract().is_err());
+        assert!(Decoder::new(&png).passphrase("wrong").extract().is_err());
+        let envelope: Envelope = Decoder::new(&png).passphrase("secret").extract().unwrap().unwrap();
+        assert!(envelope.is_encrypted());
+        assert_eq!(envelope.payload(), &[1, 2, 3]);
+    }
+    #[test]
+    fn test_trailer() {
+        let png: Png = Encoder::new(testing_png())
+            .method(Arc::new(TrailerCarrier))
+            .embed(String::from("After the end"))
+            .unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        let png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+
+        let envelopes: Vec<Envelope> = Decoder::new(&png).method(Arc::new(TrailerCarrier)).extract_all().unwrap();
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(envelopes[0].payload(), "After the end".as_bytes());
+    }
+    #[test]
+    fn test_extract_all() {
+        let png: Png = Encoder::new(testing_png()).embed(String::from("First")).unwrap();
+        let png: Png = Encoder::new(png).method(Arc::new(TrailerCarrier)).embed(String::from("Second")).unwrap();
+
+        let envelopes: Vec<Envelope> = Decoder::new(&png).extract_all().unwrap();
+        assert_eq!(envelopes.len(), 2);
+        assert!(Decoder::new(&testing_png()).extract().unwrap().is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+// Ancillary chunks kept by default as they change how the image renders. The APNG chunks are
+// kept too, dropping some of them would break the animation.
+pub const DEFAULT_KEPT_CHUNK_TYPES: &[&str] = &["tRNS", "gAMA", "sRGB", "iCCP", "acTL", "fcTL", "fdAT"];
+// Ancillary chunks holding metadata, which are only removed on request
+pub const METADATA_CHUNK_TYPES: &[&str] = &["eXIf", "tEXt", "zTXt", "iTXt", "tIME"];
+
+/// What has been stripped out of a PNG
+#[derive(Debug, Clone, Serialize)]
+pub struct Stripped {
+    pub chunks: Vec<Chunk>,
+    pub trailing_data_length: usize,
+}
+impl Stripped {
+    // Whether the PNG has been left unchanged
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.trailing_data_length == 0
+    }
+}
+
+// Removes the ancillary chunks of a PNG except the default allowlist and the `keep` chunk types,
+// along with its trailing data. Metadata chunks are kept unless `metadata` is set.
+pub fn strip(png: &mut Png, keep: &[String], metadata: bool) -> Stripped {
+    let is_kept = |chunk_type: &str| -> bool {
+        DEFAULT_KEPT_CHUNK_TYPES.contains(&chunk_type)
+            || keep.iter().any(|kept: &String| kept == chunk_type)
+            || (!metadata && METADATA_CHUNK_TYPES.contains(&chunk_type))
+    };
+    let mut removed_types: Vec<String> = vec![];
+    for chunk in png.chunks().iter().filter(|chunk: &&Chunk| !chunk.chunk_type().is_critical()) {
+        let chunk_type: String = chunk.chunk_type().to_string();
+        if !is_kept(&chunk_type) && !removed_types.contains(&chunk_type) {
+            removed_types.push(chunk_type);
+        }
+    }
+
+    let mut chunks: Vec<Chunk> = vec![];
+    for chunk_type in removed_types {
+        chunks.extend(png.remove_all_chunks(&chunk_type));
+    }
+    let trailing_data_length: usize = png.trailing_data().len();
+    png.set_trailing_data(vec![]);
+    Stripped{chunks, trailing_data_length}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::png::Png;
+    use crate::strip::{self, Stripped};
+
+    fn testing_png() -> Png {
+        let mut png: Png = Png::from_chunks(vec![
+            Chunk::new(*b"IHDR", vec![0; 13]),
+            Chunk::new(*b"gAMA", vec![0, 0, 177, 143]),
+            Chunk::new(*b"pHYs", vec![0; 9]),
+            Chunk::new(*b"tEXt", b"Comment\0Hi".to_vec()),
+            Chunk::new(*b"ruSt", vec![1, 2, 3]),
+            Chunk::new(*b"IDAT", vec![0; 10]),
+            Chunk::new(*b"tIME", vec![0; 7]),
+            Chunk::new(*b"ruSt", vec![4, 5, 6]),
+            Chunk::new(*b"IEND", vec![]),
+        ]);
+        png.set_trailing_data(vec![7, 8]);
+        png
+    }
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk: &Chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_strip() {
+        let mut png: Png = testing_png();
+        let stripped: Stripped = strip::strip(&mut png, &[], false);
+        assert_eq!(types(&png), ["IHDR", "gAMA", "tEXt", "IDAT", "tIME", "IEND"]);
+        assert_eq!(stripped.chunks.len(), 3);
+        assert_eq!(stripped.trailing_data_length, 2);
+        assert!(png.trailing_data().is_empty());
+        assert!(strip::strip(&mut png, &[], false).is_empty());
+    }
+    #[test]
+    fn test_strip_metadata() {
+        let mut png: Png = testing_png();
+        strip::strip(&mut png, &[String::from("pHYs")], true);
+        assert_eq!(types(&png), ["IHDR", "gAMA", "pHYs", "IDAT", "IEND"]);
+    }
+}
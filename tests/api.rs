@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use img_msg_encoder::{
+    decode_message, encode_message, repair, Chunk, ChunkType, Compression, Envelope, ParseMode, Png
+};
+
+fn testing_png() -> Png {
+    Png::from_chunks(vec![
+        Chunk::new(*b"IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        Chunk::new(*b"IDAT", vec![120, 156, 99, 96, 0, 0, 0, 2, 0, 1]),
+        Chunk::new(*b"IEND", vec![]),
+    ])
+}
+
+#[test]
+fn test_message_round_trip() {
+    let mut png: Png = testing_png();
+    encode_message(&mut png, "ruSt", &Envelope::from_message("Hello from the library")).unwrap();
+
+    let png: Png = Png::try_from(png.as_bytes().as_ref()).unwrap();
+    let envelope: Envelope = decode_message(&png, "ruSt").unwrap().unwrap();
+    assert_eq!(envelope.payload(), "Hello from the library".as_bytes());
+    assert!(decode_message(&png, "abCd").unwrap().is_none());
+}
+
+#[test]
+fn test_file_round_trip() {
+    let payload: Vec<u8> = "%PDF-1.4 ".repeat(100).into_bytes();
+    let envelope: Envelope = Envelope::new(payload.clone(), Some(String::from("doc.pdf")), String::from("application/pdf"))
+        .with_compression(Compression::Zstd)
+        .with_error_correction(16);
+    let mut png: Png = testing_png();
+    encode_message(&mut png, "ruSt", &envelope).unwrap();
+
+    let envelope: Envelope = decode_message(&png, "ruSt").unwrap().unwrap();
+    assert_eq!(envelope.payload(), payload);
+    assert_eq!(envelope.filename(), Some("doc.pdf"));
+    assert_eq!(envelope.compression(), Compression::Zstd);
+}
+
+#[test]
+fn test_invalid_chunk_type() {
+    let mut png: Png = testing_png();
+    assert!(encode_message(&mut png, "ruS", &Envelope::from_message("Hello")).is_err());
+    assert!(encode_message(&mut png, "ru5t", &Envelope::from_message("Hello")).is_err());
+}
+
+#[test]
+fn test_damaged_message_recovery() {
+    let mut chunks: Vec<Chunk> = testing_png().chunks();
+    chunks.insert(2, Chunk::new(
+        ChunkType::from_str("ruSt").unwrap().bytes(),
+        Envelope::from_message("Damaged but recoverable").with_error_correction(8).to_bytes().unwrap()
+    ));
+    let mut bytes: Vec<u8> = Png::from_chunks(chunks).as_bytes();
+    // Somewhere in the data of the hidden chunk, before its CRC and the IEND chunk
+    let position: usize = bytes.len() - 12 - 10;
+    bytes[position] ^= 0xFF;
+
+    assert!(Png::try_from(bytes.as_ref()).is_err());
+    let damaged: Png = Png::parse(&bytes, ParseMode::Lenient).unwrap();
+    let envelope: Envelope = decode_message(&damaged, "ruSt").unwrap().unwrap();
+    assert_eq!(envelope.payload(), "Damaged but recoverable".as_bytes());
+    assert_eq!(envelope.corrected_bytes(), 1);
+
+    let (repaired, fixes) = repair::repair(&damaged).unwrap();
+    assert!(!fixes.is_empty());
+    assert!(repair::check(&repaired).is_empty());
+    assert!(Png::try_from(repaired.as_bytes().as_ref()).is_ok());
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use img_msg_encoder::{Chunk, Png};
+
+// A 1x1 grayscale PNG followed by trailing data, written to a file of the temporary directory
+fn png_with_trailing_data(name: &str) -> PathBuf {
+    let mut png: Png = Png::from_chunks(vec![
+        Chunk::new(*b"IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        Chunk::new(*b"IDAT", vec![120, 156, 99, 96, 0, 0, 0, 2, 0, 1]),
+        Chunk::new(*b"IEND", vec![]),
+    ]);
+    png.set_trailing_data(b"After the end".to_vec());
+    let path: PathBuf = std::env::temp_dir().join(format!("img_msg_encoder_cli_{}_{}.png", std::process::id(), name));
+    fs::write(&path, png.as_bytes()).unwrap();
+    path
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_img_msg_encoder")).args(args).output().unwrap()
+}
+
+#[test]
+fn test_scan_trailing_data() {
+    let input: PathBuf = png_with_trailing_data("scan");
+    let output: Output = run(&["scan", input.to_str().unwrap()]);
+    fs::remove_file(&input).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("13 bytes of trailing data after IEND"));
+}
+
+#[test]
+fn test_strip_trailing_data() {
+    let input: PathBuf = png_with_trailing_data("strip");
+    let stripped: PathBuf = input.with_extension("stripped.png");
+    let output: Output = run(&["strip", input.to_str().unwrap(), "-o", stripped.to_str().unwrap()]);
+    fs::remove_file(&input).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Removed 13 bytes of trailing data"));
+    let png: Png = Png::from_file(&stripped).unwrap();
+    fs::remove_file(&stripped).unwrap();
+    assert!(png.trailing_data().is_empty());
+}
+
+#[test]
+fn test_chunk_type_option() {
+    let input: PathBuf = png_with_trailing_data("chunk_type");
+    let encoded: PathBuf = input.with_extension("encoded.png");
+    let removed: PathBuf = input.with_extension("removed.png");
+    let output: Output = run(&[
+        "encode", input.to_str().unwrap(), "Hello", "--chunk-type", "ruSt", "-o", encoded.to_str().unwrap()
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output: Output = run(&["decode", encoded.to_str().unwrap(), "--chunk-type", "ruSt"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Hello"));
+    let output: Output = run(&["decode", encoded.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--chunk-type"));
+
+    let output: Output = run(&[
+        "remove", encoded.to_str().unwrap(), "--chunk-type", "ruSt", "-o", removed.to_str().unwrap()
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(Png::from_file(&removed).unwrap().chunk_by_type("ruSt").is_none());
+
+    for path in [&input, &encoded, &removed] {
+        fs::remove_file(path).unwrap();
+    }
+}